@@ -0,0 +1,187 @@
+use serde::Deserialize;
+use std::{env, error::Error, fs, path::Path, path::PathBuf, str::FromStr};
+
+use crate::data::{ItemType, Sort};
+
+pub const OMDB_KEY_VAR: &str = "OMDB_KEY";
+pub const SYNC_LIMIT_VAR: &str = "NF_RATED_SYNC_LIMIT";
+pub const ITEM_TYPE_VAR: &str = "NF_RATED_TYPE";
+pub const SORT_VAR: &str = "NF_RATED_SORT";
+pub const PRIOR_VOTES_VAR: &str = "NF_RATED_PRIOR_VOTES";
+pub const DB_PATH_VAR: &str = "NF_RATED_DB";
+
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// One or more OMDb API keys, given comma separated, i.e. `OMDB_KEY=<key>,<other key>`,
+/// or as a list in the config.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "ApiKeysConfig")]
+pub struct ApiKeys(pub Vec<String>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ApiKeysConfig {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<ApiKeysConfig> for ApiKeys {
+    fn from(config: ApiKeysConfig) -> Self {
+        match config {
+            ApiKeysConfig::One(keys) => ApiKeys::from_comma_separated(&keys),
+            ApiKeysConfig::Many(keys) => ApiKeys(keys),
+        }
+    }
+}
+
+impl ApiKeys {
+    fn from_comma_separated(keys: &str) -> Self {
+        ApiKeys(
+            keys.split(',')
+                .map(|key| key.trim().to_string())
+                .filter(|key| !key.is_empty())
+                .collect(),
+        )
+    }
+}
+
+impl FromStr for ApiKeys {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = ApiKeys::from_comma_separated(s);
+        if keys.0.is_empty() {
+            Err(format!("No API key in '{}'", s))
+        } else {
+            Ok(keys)
+        }
+    }
+}
+
+/// Settings read from the `config.toml` inside the app data folder, i.e.:
+///
+/// ```toml
+/// omdb_key = "<api key>"  # or a list of keys, i.e. ["<api key>", "<other api key>"]
+/// sync_limit = 500
+/// item_type = "movie"
+/// sort = "weighted"
+/// prior_votes = 25000
+/// db_path = "/home/me/nf_rated.sqlite"
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub omdb_key: Option<ApiKeys>,
+    pub sync_limit: Option<usize>,
+    pub item_type: Option<ItemType>,
+    pub sort: Option<Sort>,
+    pub prior_votes: Option<u32>,
+    pub db_path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Loads the config at the given path, a missing file results in an empty config.
+    pub fn load(path: &Path) -> Result<Config, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let toml = fs::read_to_string(path)?;
+        toml::from_str(&toml).map_err(|err| format!("Invalid config {:?}: {}", path, err).into())
+    }
+}
+
+/// Resolves a setting from the CLI flag named `name`, then the environment variable, then the
+/// config file.
+/// An invalid flag is an error while an invalid environment variable is skipped with a warning,
+/// as it may have been set for another purpose long ago.
+pub fn resolve_optional_setting<T>(
+    name: &str,
+    flag: Option<&str>,
+    env_var: &str,
+    config: Option<T>,
+) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: FromStr,
+{
+    if let Some(value) = flag {
+        return match value.parse::<T>() {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(format!("Invalid value '{}' for --{}", value, name).into()),
+        };
+    }
+    let env_value = env::var(env_var)
+        .ok()
+        .and_then(|value| match value.parse::<T>() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                eprintln!("Ignoring invalid value '{}' of {}", value, env_var);
+                None
+            }
+        });
+    Ok(env_value.or(config))
+}
+
+/// Same as [resolve_optional_setting], falling back to the built-in default if none of the
+/// sources has the setting.
+pub fn resolve_setting<T>(
+    name: &str,
+    flag: Option<&str>,
+    env_var: &str,
+    config: Option<T>,
+    default: T,
+) -> Result<T, Box<dyn Error>>
+where
+    T: FromStr,
+{
+    Ok(resolve_optional_setting(name, flag, env_var, config)?.unwrap_or(default))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test uses its own variable as tests run in parallel
+    fn resolve(
+        flag: Option<&str>,
+        env_var: &str,
+        env_value: Option<&str>,
+        config: Option<u32>,
+    ) -> u32 {
+        match env_value {
+            Some(value) => env::set_var(env_var, value),
+            None => env::remove_var(env_var),
+        }
+        resolve_setting("limit", flag, env_var, config, 100).unwrap()
+    }
+
+    #[test]
+    fn flag_over_env_over_config_over_default() {
+        let var = "NF_RATED_TEST_PRECEDENCE";
+        assert_eq!(resolve(Some("1"), var, Some("2"), Some(3)), 1);
+        assert_eq!(resolve(None, var, Some("2"), Some(3)), 2);
+        assert_eq!(resolve(None, var, None, Some(3)), 3);
+        assert_eq!(resolve(None, var, None, None), 100);
+    }
+
+    #[test]
+    fn invalid_env_falls_back() {
+        let var = "NF_RATED_TEST_INVALID_ENV";
+        assert_eq!(resolve(None, var, Some("many"), Some(3)), 3);
+        assert_eq!(resolve(None, var, Some("many"), None), 100);
+        assert_eq!(resolve(Some("1"), var, Some("many"), None), 1);
+    }
+
+    #[test]
+    fn invalid_flag_fails() {
+        let var = "NF_RATED_TEST_INVALID_FLAG";
+        env::remove_var(var);
+        let err = resolve_setting("limit", Some("many"), var, Some(3), 100).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid value 'many' for --limit");
+    }
+
+    #[test]
+    fn invalid_config_fails_to_load() {
+        assert!(toml::from_str::<Config>("sort = \"loudest\"").is_err());
+        assert!(toml::from_str::<Config>("sync_limit = \"many\"").is_err());
+    }
+}
@@ -22,6 +22,25 @@ pub struct RatedRow {
     pub imdb_votes: Option<u32>,
     pub imdb_id: Option<String>,
 
-    // millis since UNIX_EPOCH
-    pub last_sync: Option<u32>,
+    // secs since UNIX_EPOCH
+    pub last_sync: Option<i64>,
+
+    // What OMDb matched the title with
+    pub omdb_title: Option<String>,
+    pub omdb_year: Option<u32>,
+    pub match_confidence: Option<u32>,
+    // Comma separated, i.e. "year,type"
+    pub match_issues: Option<String>,
+    // Title variant OMDb found the title by
+    pub lookup_title: Option<String>,
+
+    // Parsed from the duration, episode runtime for series
+    pub runtime_minutes: Option<u32>,
+    pub season_count: Option<u32>,
+
+    // When Netflix added the title, i.e. "2016-09-09"
+    pub date_added: Option<String>,
+
+    // Rating pulled towards the catalog mean the fewer votes it has, same scale as imdb_rating
+    pub weighted_rating: Option<f64>,
 }
@@ -0,0 +1,85 @@
+use super::{
+    fold, CAST_COLUMN, COUNTRY_COLUMN, DIRECTOR_COLUMN, GENRE_COLUMN, LANGUAGE_COLUMN, PLOT_COLUMN,
+    TITLE_COLUMN, WRITER_COLUMN,
+};
+use crate::core::RatedRow;
+
+/// Not an actual column, terms searching it match if any of the `ANY_COLUMNS` has them.
+pub const ANY_COLUMN: &str = "any";
+
+/// The text columns searched by the any field, in the order matches are reported in.
+pub const ANY_COLUMNS: &[&str] = &[
+    TITLE_COLUMN,
+    GENRE_COLUMN,
+    CAST_COLUMN,
+    DIRECTOR_COLUMN,
+    WRITER_COLUMN,
+    COUNTRY_COLUMN,
+    LANGUAGE_COLUMN,
+    PLOT_COLUMN,
+];
+
+fn column_text<'a>(row: &'a RatedRow, column: &str) -> Option<&'a str> {
+    match column {
+        TITLE_COLUMN => Some(&row.title),
+        GENRE_COLUMN => row.genre.as_deref(),
+        CAST_COLUMN => Some(&row.cast),
+        DIRECTOR_COLUMN => Some(&row.director),
+        WRITER_COLUMN => row.writer.as_deref(),
+        COUNTRY_COLUMN => Some(&row.country),
+        LANGUAGE_COLUMN => row.language.as_deref(),
+        PLOT_COLUMN => Some(&row.plot),
+        _ => None,
+    }
+}
+
+/// The columns of the row that contain any of the terms, compared the way queries do.
+pub fn matched_fields(row: &RatedRow, terms: &[String]) -> Vec<&'static str> {
+    let terms: Vec<String> = terms.iter().map(|term| fold(term)).collect();
+    ANY_COLUMNS
+        .iter()
+        .filter(|column| match column_text(row, column) {
+            Some(text) => {
+                let text = fold(text);
+                terms.iter().any(|term| text.contains(term.as_str()))
+            }
+            None => false,
+        })
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::CsvRow;
+    use csv::StringRecord;
+
+    #[test]
+    fn fields_containing_terms() {
+        let record = StringRecord::from(vec![
+            "1",
+            "Movie",
+            "The Heist",
+            "Christopher Nolan",
+            "Someone",
+            "South Korea",
+            "September 9, 2016",
+            "2016",
+            "TV-MA",
+            "100 min",
+            "Thrillers",
+            "A Korean crew plans one last heist.",
+        ]);
+        let row: RatedRow = CsvRow::from(record).into();
+        let terms = vec!["HEIST".to_string(), "korea".to_string()];
+        assert_eq!(
+            matched_fields(&row, &terms),
+            vec![TITLE_COLUMN, COUNTRY_COLUMN, PLOT_COLUMN]
+        );
+        assert_eq!(
+            matched_fields(&row, &["nolan".to_string()]),
+            vec![DIRECTOR_COLUMN]
+        );
+    }
+}
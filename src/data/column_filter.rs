@@ -1,8 +1,9 @@
-use super::{destructure_query_filter, QueryTerm, CAST_COLUMN};
+use super::{parse_query, Query, QueryExpr, QuerySyntaxError, ANY_COLUMN};
 
 pub struct ColumnFilter {
     column: String,
     query: String,
+    fuzzy: bool,
 }
 
 impl From<(&str, &str)> for ColumnFilter {
@@ -10,6 +11,7 @@ impl From<(&str, &str)> for ColumnFilter {
         Self {
             column: tp.0.to_string(),
             query: tp.1.to_string(),
+            fuzzy: false,
         }
     }
 }
@@ -20,34 +22,71 @@ impl From<(&str, &String)> for ColumnFilter {
         Self {
             column: tp.0.to_string(),
             query: tp.1.to_string(),
+            fuzzy: false,
         }
     }
 }
 
 impl ColumnFilter {
-    pub fn sql_fragments(&self, matched_before: bool) -> Vec<String> {
-        let column = if self.column == CAST_COLUMN {
-            "`cast`"
-        } else {
-            &self.column
+    /// Also matches titles and cast that are similar to the terms, see `QueryExpr::to_sql`.
+    pub fn fuzzy(self, fuzzy: bool) -> Self {
+        Self { fuzzy, ..self }
+    }
+
+    fn parse(&self) -> Result<Option<QueryExpr>, QuerySyntaxError> {
+        parse_query(&self.query).map_err(|err| QuerySyntaxError {
+            column: Some(self.column.clone()),
+            ..err
+        })
+    }
+
+    /// The conditions all of which have to match, without the `AND`s to join them.
+    pub fn sql_conditions(&self) -> Result<Vec<Query>, QuerySyntaxError> {
+        let expr = self.parse()?;
+        let exprs = match expr {
+            Some(QueryExpr::And(exprs)) => exprs,
+            Some(expr) => vec![expr],
+            None => vec![],
         };
-        let mut first = !matched_before;
-        let terms = destructure_query_filter(&self.query);
-        let filters: Vec<String> = terms
+        Ok(exprs
             .iter()
-            .map(|term| match term {
-                QueryTerm::Not(term) => {
-                    let and = if first { "" } else { " AND" };
-                    first = false;
-                    format!("\n {} NOT {} LIKE '%{}%'", and, column, term)
-                }
-                QueryTerm::And(term) => {
-                    let and = if first { "" } else { " AND" };
-                    first = false;
-                    format!("\n {} {} LIKE '%{}%'", and, column, term)
-                }
+            .map(|expr| {
+                let mut params = vec![];
+                let sql = expr.to_sql(&self.column, self.fuzzy, &mut params);
+                Query { sql, params }
             })
-            .collect();
-        filters
+            .collect())
+    }
+
+    /// The terms searching the any field, to show which fields of the results they matched.
+    pub fn any_terms(&self) -> Vec<String> {
+        match self.parse() {
+            Ok(Some(expr)) => expr
+                .positive_terms(&self.column)
+                .into_iter()
+                .filter(|(column, _)| *column == ANY_COLUMN)
+                .map(|(_, text)| text.to_string())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Scores of how well the fuzzy terms match, `None` unless in fuzzy mode.
+    pub fn fuzzy_rank(&self) -> Result<Option<Query>, QuerySyntaxError> {
+        if !self.fuzzy {
+            return Ok(None);
+        }
+        let mut params = vec![];
+        let scores = match self.parse()? {
+            Some(expr) => expr.fuzzy_rank_sql(&self.column, &mut params),
+            None => vec![],
+        };
+        if scores.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Query {
+            sql: scores.join(" + "),
+            params,
+        }))
     }
 }
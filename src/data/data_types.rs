@@ -2,6 +2,7 @@ use csv::StringRecord;
 use rusqlite::Row;
 use serde::Deserialize;
 
+use super::{assess_match, parse_date_added, parse_duration, parse_omdb_year};
 use crate::core::RatedRow;
 
 const N_A: &str = "N/A";
@@ -17,11 +18,14 @@ pub struct CsvRow {
     typ: String,
     duration: String,
     plot: String,
+    date_added: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
 #[allow(non_snake_case)]
 pub struct OmdbSuccessResponseJson {
+    Title: String,
+    Year: String,
     Type: String,
     Runtime: String,
     Plot: String,
@@ -35,6 +39,24 @@ pub struct OmdbSuccessResponseJson {
     imdbID: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct ImdbBasicsRow {
+    pub id: String,
+    pub typ: String,
+    pub title: String,
+    pub start_year: Option<u32>,
+    pub end_year: Option<u32>,
+    pub runtime_minutes: Option<u32>,
+    pub genres: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImdbRatingsRow {
+    pub id: String,
+    pub rating: Option<f32>,
+    pub votes: Option<u32>,
+}
+
 #[derive(Deserialize, Debug)]
 #[allow(non_snake_case)]
 pub struct OmdbErrorResponseJson {
@@ -50,6 +72,9 @@ fn json_string(s: &str) -> Option<String> {
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct JsonRow {
+    title: Option<String>,
+    // As OMDb formats it, i.e. "2019–2020" for series
+    year: Option<String>,
     typ: Option<String>,
     duration: Option<String>,
     plot: Option<String>,
@@ -80,6 +105,8 @@ impl From<OmdbSuccessResponseJson> for JsonRow {
         let imdb_id = json_string(&json.imdbID);
 
         Self {
+            title: json_string(&json.Title),
+            year: json_string(&json.Year),
             typ: json_string(&json.Type),
             duration: json_string(&json.Runtime),
             plot: json_string(&json.Plot),
@@ -95,6 +122,42 @@ impl From<OmdbSuccessResponseJson> for JsonRow {
     }
 }
 
+impl From<(ImdbBasicsRow, ImdbRatingsRow)> for JsonRow {
+    fn from((basics, ratings): (ImdbBasicsRow, ImdbRatingsRow)) -> Self {
+        let imdb_rating = ratings.rating.map(|rating| (rating * 10.0).round() as u32);
+        // Seasons aren't part of the dataset, so we keep the Netflix duration for series
+        let duration = match (basics.typ.as_str(), basics.runtime_minutes) {
+            ("movie", Some(minutes)) => Some(format!("{} min", minutes)),
+            _ => None,
+        };
+
+        let year = basics.start_year.map(|start_year| match basics.end_year {
+            Some(end_year) => format!("{}–{}", start_year, end_year),
+            None => start_year.to_string(),
+        });
+
+        Self {
+            title: Some(basics.title),
+            year,
+            typ: Some(basics.typ),
+            duration,
+            plot: None,
+
+            // OMDb formats genres as "Drama, Romance", IMDb datasets as "Drama,Romance"
+            genre: basics
+                .genres
+                .map(|genres| genres.replace(",", ", "))
+                .unwrap_or_else(|| N_A.to_string()),
+            language: N_A.to_string(),
+            writer: N_A.to_string(),
+
+            imdb_rating,
+            imdb_votes: ratings.votes,
+            imdb_id: Some(basics.id),
+        }
+    }
+}
+
 fn maybe_uint(s: &str) -> Option<u32> {
     match s {
         N_A => None,
@@ -123,12 +186,71 @@ impl From<StringRecord> for CsvRow {
             typ: x.get(1).unwrap().to_string(),
             duration: x.get(9).unwrap().to_string(),
             plot: x.get(11).unwrap().to_string(),
+            date_added: parse_date_added(x.get(6).unwrap()),
+        }
+    }
+}
+
+// IMDb datasets mark missing values with \N
+const IMDB_NULL: &str = "\\N";
+
+fn imdb_uint(s: &str) -> Option<u32> {
+    match s {
+        IMDB_NULL => None,
+        s => s.parse::<u32>().ok(),
+    }
+}
+
+fn imdb_string(s: &str) -> Option<String> {
+    match s {
+        IMDB_NULL => None,
+        s => Some(s.to_string()),
+    }
+}
+
+/// Maps an IMDb `titleType` onto the types OMDb reports, `None` for types Netflix doesn't carry.
+pub fn imdb_title_type(title_type: &str) -> Option<&'static str> {
+    match title_type {
+        "movie" | "tvMovie" => Some("movie"),
+        "tvSeries" | "tvMiniSeries" => Some("series"),
+        _ => None,
+    }
+}
+
+// title.basics.tsv
+// 0: tconst, 1: titleType, 2: primaryTitle, 3: originalTitle, 4: isAdult,
+// 5: startYear, 6: endYear, 7: runtimeMinutes, 8: genres
+impl From<&StringRecord> for ImdbBasicsRow {
+    fn from(x: &StringRecord) -> Self {
+        Self {
+            id: x.get(0).unwrap().to_string(),
+            typ: imdb_title_type(x.get(1).unwrap())
+                .unwrap_or_default()
+                .to_string(),
+            title: x.get(2).unwrap().to_string(),
+            start_year: imdb_uint(x.get(5).unwrap()),
+            end_year: imdb_uint(x.get(6).unwrap()),
+            runtime_minutes: imdb_uint(x.get(7).unwrap()),
+            genres: imdb_string(x.get(8).unwrap()),
+        }
+    }
+}
+
+// title.ratings.tsv
+// 0: tconst, 1: averageRating, 2: numVotes
+impl From<&StringRecord> for ImdbRatingsRow {
+    fn from(x: &StringRecord) -> Self {
+        Self {
+            id: x.get(0).unwrap().to_string(),
+            rating: x.get(1).unwrap().parse::<f32>().ok(),
+            votes: imdb_uint(x.get(2).unwrap()),
         }
     }
 }
 
 impl From<CsvRow> for RatedRow {
     fn from(csv: CsvRow) -> Self {
+        let (runtime_minutes, season_count) = parse_duration(&csv.duration);
         RatedRow {
             id: csv.id,
             title: csv.title,
@@ -149,6 +271,17 @@ impl From<CsvRow> for RatedRow {
             imdb_id: None,
 
             last_sync: None,
+
+            omdb_title: None,
+            omdb_year: None,
+            match_confidence: None,
+            match_issues: None,
+            lookup_title: None,
+
+            runtime_minutes,
+            season_count,
+            date_added: csv.date_added,
+            weighted_rating: None,
         }
     }
 }
@@ -171,13 +304,40 @@ pub fn rated_row_from_row(row: &Row) -> RatedRow {
         imdb_votes: row.get(13).unwrap(),
         imdb_id: row.get(14).unwrap(),
         last_sync: row.get(15).unwrap(),
+        omdb_title: row.get(16).unwrap(),
+        omdb_year: row.get(17).unwrap(),
+        match_confidence: row.get(18).unwrap(),
+        match_issues: row.get(19).unwrap(),
+        lookup_title: row.get(20).unwrap(),
+        runtime_minutes: row.get(21).unwrap(),
+        season_count: row.get(22).unwrap(),
+        date_added: row.get(23).unwrap(),
+        // Only computed by the queries that list titles, see `query_head`
+        weighted_rating: row.get("weighted").unwrap_or_default(),
     }
 }
 
-impl From<(RatedRow, JsonRow, u32)> for RatedRow {
-    fn from((rated, json, last_sync): (RatedRow, JsonRow, u32)) -> Self {
+impl From<(RatedRow, JsonRow, i64)> for RatedRow {
+    fn from((rated, json, last_sync): (RatedRow, JsonRow, i64)) -> Self {
+        let mut netflix_titles = vec![rated.title.as_str()];
+        netflix_titles.extend(rated.lookup_title.as_deref());
+        // Compared against the Netflix type, so before it gets replaced by the OMDb one
+        let quality = match (&json.title, &json.year, &json.typ) {
+            (Some(title), Some(year), Some(typ)) => Some(assess_match(
+                &netflix_titles,
+                rated.year,
+                &rated.typ,
+                title,
+                year,
+                typ,
+            )),
+            _ => None,
+        };
+        let omdb_year = json.year.as_ref().and_then(|year| parse_omdb_year(year).0);
+
         let typ = json.typ.unwrap_or(rated.typ);
         let duration = json.duration.unwrap_or(rated.duration);
+        let (runtime_minutes, season_count) = parse_duration(&duration);
         let plot = json.plot.unwrap_or(rated.plot);
 
         Self {
@@ -197,6 +357,17 @@ impl From<(RatedRow, JsonRow, u32)> for RatedRow {
             imdb_votes: json.imdb_votes,
             imdb_id: json.imdb_id,
             last_sync: Some(last_sync),
+            omdb_title: json.title,
+            omdb_year,
+            match_confidence: quality.as_ref().map(|quality| quality.confidence),
+            match_issues: quality.and_then(|quality| quality.issues_column()),
+            lookup_title: rated.lookup_title,
+            runtime_minutes: runtime_minutes.or(rated.runtime_minutes),
+            // OMDb has the runtime of an episode instead of the seasons Netflix lists
+            season_count: season_count.or(rated.season_count),
+            date_added: rated.date_added,
+            // The rating changed, it's weighted again once queried
+            weighted_rating: None,
         }
     }
 }
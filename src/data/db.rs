@@ -1,8 +1,8 @@
-use rusqlite::{params, Connection, Error, Result, NO_PARAMS};
+use rusqlite::{functions::FunctionFlags, params, Connection, Error, Result, Statement, NO_PARAMS};
 
-use super::{rated_row_from_row, CsvRow};
+use super::{fold, fuzzy_score, rated_row_from_row, CsvRow, Preset, Query};
 use crate::core::RatedRow;
-use std::{error::Error as StdError, fs};
+use std::{error::Error as StdError, fs, time::Duration};
 
 use super::DatabaseInfo;
 
@@ -28,6 +28,53 @@ const CREATE_TABLE_QUERY: &str = "CREATE TABLE IF NOT EXISTS nf_imdb (
     last_sync        INTEGER
 )";
 
+// Each migration upgrades the schema by one version which is tracked via `PRAGMA user_version`.
+// Columns are only ever appended so that the positional reads in `rated_row_from_row` stay valid.
+const MIGRATIONS: &[&str] = &[
+    "
+ALTER TABLE nf_imdb ADD COLUMN omdb_title TEXT;
+ALTER TABLE nf_imdb ADD COLUMN omdb_year INTEGER;
+ALTER TABLE nf_imdb ADD COLUMN match_confidence INTEGER;
+ALTER TABLE nf_imdb ADD COLUMN match_issues TEXT;
+",
+    "
+ALTER TABLE nf_imdb ADD COLUMN lookup_title TEXT;
+",
+    // last_sync used to be stored as seconds since 1599939357, when nf-rated was created
+    "
+UPDATE nf_imdb SET last_sync = last_sync + 1599939357 WHERE last_sync IS NOT NULL;
+",
+    "
+ALTER TABLE nf_imdb ADD COLUMN runtime_minutes INTEGER;
+ALTER TABLE nf_imdb ADD COLUMN season_count INTEGER;
+UPDATE nf_imdb SET
+    runtime_minutes = CASE WHEN duration LIKE '% min' THEN CAST(duration AS INTEGER) END,
+    season_count = CASE WHEN duration LIKE '% season%' THEN CAST(duration AS INTEGER) END;
+",
+    // Backfilled from the Netflix titles by `backfill_date_added`
+    "
+ALTER TABLE nf_imdb ADD COLUMN date_added TEXT;
+",
+    // No longer written, queries compute the weighted rating instead, see `query_head`
+    "
+ALTER TABLE nf_imdb ADD COLUMN weighted_rating REAL;
+",
+];
+const DATE_ADDED_VERSION: usize = 5;
+
+const CREATE_REQUESTS_TABLE_QUERY: &str = "CREATE TABLE IF NOT EXISTS omdb_requests (
+    api_key          TEXT NOT NULL,
+    day              INTEGER NOT NULL,
+    count            INTEGER NOT NULL,
+
+    PRIMARY KEY (api_key, day)
+)";
+
+const CREATE_PRESETS_TABLE_QUERY: &str = "CREATE TABLE IF NOT EXISTS presets (
+    name             TEXT PRIMARY KEY,
+    preset           TEXT NOT NULL
+)";
+
 const UPSERT_QUERY: &str = "INSERT INTO nf_imdb (
     id          ,
     title       ,
@@ -47,8 +94,18 @@ const UPSERT_QUERY: &str = "INSERT INTO nf_imdb (
     imdb_votes  ,
     imdb_id     ,
 
-    last_sync)
-VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+    last_sync   ,
+
+    omdb_title  ,
+    omdb_year   ,
+    match_confidence,
+    match_issues,
+    lookup_title,
+    runtime_minutes,
+    season_count,
+    date_added)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20,
+    ?21, ?22, ?23, ?24)
 ON CONFLICT (id) DO NOTHING;
 ";
 
@@ -72,49 +129,226 @@ const SYNC_QUERY: &str = "UPDATE nf_imdb
         imdb_votes = ?14,
         imdb_id    = ?15,
 
-        last_sync  = ?16
+        last_sync  = ?16,
+
+        omdb_title = ?17,
+        omdb_year  = ?18,
+        match_confidence = ?19,
+        match_issues = ?20,
+        lookup_title = ?21,
+        runtime_minutes = ?22,
+        season_count = ?23,
+        date_added = ?24
     WHERE
         id = ?1;
 ";
 
+const ADD_REQUESTS_QUERY: &str = "INSERT INTO omdb_requests (api_key, day, count)
+VALUES (?1, ?2, ?3)
+ON CONFLICT (api_key, day) DO UPDATE SET count = count + excluded.count;
+";
+
+const EXHAUST_REQUESTS_QUERY: &str = "INSERT INTO omdb_requests (api_key, day, count)
+VALUES (?1, ?2, ?3)
+ON CONFLICT (api_key, day) DO UPDATE SET count = MAX(count, excluded.count);
+";
+
+const SELECT_REQUESTS_QUERY: &str =
+    "SELECT count FROM omdb_requests WHERE api_key = ?1 AND day = ?2;";
+
+const UPSERT_PRESET_QUERY: &str = "INSERT INTO presets (name, preset)
+VALUES (?1, ?2)
+ON CONFLICT (name) DO UPDATE SET preset = excluded.preset;
+";
+const SELECT_PRESETS_QUERY: &str = "SELECT name, preset FROM presets ORDER BY name COLLATE NOCASE;";
+const SELECT_PRESET_QUERY: &str = "SELECT name, preset FROM presets WHERE name = ?1;";
+const DELETE_PRESET_QUERY: &str = "DELETE FROM presets WHERE name = ?1;";
+
 const SELECT_UNSYNCED_QUERY: &str = "SELECT * FROM nf_imdb WHERE last_sync IS NULL;";
 const SELECT_SYNCED_QUERY: &str = "SELECT * FROM nf_imdb WHERE last_sync IS NOT NULL;";
+const SELECT_STALE_QUERY: &str =
+    "SELECT * FROM nf_imdb WHERE last_sync < ?1 ORDER BY last_sync ASC;";
+const SELECT_SYNC_RANGE_QUERY: &str = "SELECT MIN(last_sync), MAX(last_sync) FROM nf_imdb;";
+const TOUCH_ROW_QUERY: &str = "UPDATE nf_imdb SET last_sync = ?2 WHERE id = ?1;";
 const DELETE_ROW_QUERY: &str = "DELETE FROM nf_imdb WHERE id = ?1;";
+const SET_DATE_ADDED_QUERY: &str = "UPDATE nf_imdb SET date_added = ?2 WHERE id = ?1;";
 const SELECT_ALL_QUERY: &str = "SELECT * FROM nf_imdb;";
 const SELECT_SYNCED_SORTED_BY_RATING_QUERY: &str =
     "SELECT * FROM nf_imdb WHERE last_sync IS NOT NULL ORDER BY imdb_rating DESC;";
 
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+const PROGRESS_BATCH_SIZE: usize = 500;
+
+/// Binds the row's columns in the order `UPSERT_QUERY` and `SYNC_QUERY` expect them.
+fn execute_row(stmt: &mut Statement, row: &RatedRow) -> Result<usize> {
+    stmt.execute(params![
+        row.id,
+        row.title,
+        row.year,
+        row.cast,
+        row.country,
+        row.director,
+        row.typ,
+        row.duration,
+        row.plot,
+        row.genre,
+        row.writer,
+        row.language,
+        row.imdb_rating,
+        row.imdb_votes,
+        row.imdb_id,
+        row.last_sync,
+        row.omdb_title,
+        row.omdb_year,
+        row.match_confidence,
+        row.match_issues,
+        row.lookup_title,
+        row.runtime_minutes,
+        row.season_count,
+        row.date_added
+    ])
+}
+
+fn preset_from_json(name: String, json: &str) -> Result<Preset, Box<dyn StdError>> {
+    let preset: Preset = serde_json::from_str(json)
+        .map_err(|err| format!("Preset '{}' is invalid: {}", name, err))?;
+    Ok(Preset { name, ..preset })
+}
+
+/// Adds the functions queries use on top of the ones SQLite comes with.
+fn register_functions(con: &Connection) -> Result<()> {
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    // fold(text), NULL stays NULL
+    con.create_scalar_function("fold", 1, flags, |ctx| {
+        let text: Option<String> = ctx.get(0)?;
+        Ok(text.map(|text| fold(&text)))
+    })?;
+    // fuzzy_score(term, value), a NULL value doesn't match at all
+    con.create_scalar_function("fuzzy_score", 2, flags, |ctx| {
+        let term: String = ctx.get(0)?;
+        let value: Option<String> = ctx.get(1)?;
+        Ok(value.map_or(0.0, |value| fuzzy_score(&term, &value)))
+    })
+}
+
 pub struct Db {
     con: Connection,
 }
 
 impl Db {
     pub fn new(info: &DatabaseInfo) -> Result<Db, Box<dyn StdError>> {
+        Self::new_with_progress(info, |_, _| {})
+    }
+
+    /// Same as `new`, but reports `(imported, total)` while a new database is filled with the
+    /// Netflix titles.
+    pub fn new_with_progress<F>(
+        info: &DatabaseInfo,
+        on_progress: F,
+    ) -> Result<Db, Box<dyn StdError>>
+    where
+        F: FnMut(usize, usize),
+    {
         if !info.folder_exists {
             fs::create_dir_all(&info.folder)?;
         }
 
         let con = Connection::open(&info.db_path)?;
+        // Lets the TUI read while a sync writes and makes either wait for the other's writes
+        // instead of failing right away
+        con.busy_timeout(BUSY_TIMEOUT)?;
+        register_functions(&con)?;
+        con.query_row("PRAGMA journal_mode = WAL;", NO_PARAMS, |row| {
+            row.get::<_, String>(0)
+        })?;
         let db = Self { con };
         if !info.db_exists {
-            db.init_data()?;
+            db.init_data(on_progress)?;
         }
+        db.migrate()?;
+        // Added after the first release, so existing databases need it as well
+        db.create_requests_table()?;
+        db.create_presets_table()?;
         Ok(db)
     }
 
-    fn init_data(&self) -> Result<(), Box<dyn StdError>> {
-        self.create_table()?;
+    fn migrate(&self) -> Result<(), Box<dyn StdError>> {
+        let version: i64 = self
+            .con
+            .query_row("PRAGMA user_version;", NO_PARAMS, |row| row.get(0))?;
+        for (idx, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let version = idx + 1;
+            // Data is backfilled before the version is bumped, so that a migration that was
+            // interrupted runs again as a whole
+            self.in_transaction(|| {
+                self.con.execute_batch(migration)?;
+                if version == DATE_ADDED_VERSION {
+                    self.backfill_date_added()?;
+                }
+                self.con
+                    .execute_batch(&format!("PRAGMA user_version = {};", version))?;
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
 
+    fn netflix_titles() -> Result<Vec<CsvRow>, Box<dyn StdError>> {
         let csv = include_str!("../../resources/data/netflix_titles.csv");
         let mut rdr = csv::Reader::from_reader(csv.as_bytes());
+        let mut rows = vec![];
         for result in rdr.records() {
-            let row: CsvRow = result?.into();
-            self.upsert_row(&row.into())?;
+            rows.push(result?.into());
         }
+        Ok(rows)
+    }
 
+    // The Netflix export is the only source of the date, so it can't be derived via SQL.
+    // Runs inside the migration's transaction.
+    fn backfill_date_added(&self) -> Result<(), Box<dyn StdError>> {
+        let mut stmt = self.con.prepare(SET_DATE_ADDED_QUERY)?;
+        for row in Self::netflix_titles()? {
+            let row = RatedRow::from(row);
+            if row.date_added.is_some() {
+                stmt.execute(params![row.id, row.date_added])?;
+            }
+        }
         Ok(())
     }
 
+    fn init_data<F>(&self, on_progress: F) -> Result<(), Box<dyn StdError>>
+    where
+        F: FnMut(usize, usize),
+    {
+        self.create_table()?;
+        self.migrate()?;
+
+        let rows: Vec<RatedRow> = Self::netflix_titles()?
+            .into_iter()
+            .map(RatedRow::from)
+            .collect();
+        self.upsert_rows(&rows, on_progress)
+    }
+
+    /// Runs `f` inside a transaction that is committed if it succeeds and rolled back otherwise,
+    /// so that batch writes are atomic and don't wait on the disk for every row.
+    pub fn in_transaction<T, F>(&self, f: F) -> Result<T, Box<dyn StdError>>
+    where
+        F: FnOnce() -> Result<T, Box<dyn StdError>>,
+    {
+        let tx = self.con.unchecked_transaction()?;
+        let result = f()?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// Changes whenever another connection commits to the database, i.e. a sync that runs
+    /// in another process.
+    pub fn data_version(&self) -> Result<i64> {
+        self.con
+            .query_row("PRAGMA data_version;", NO_PARAMS, |row| row.get(0))
+    }
+
     pub fn create_table(&self) -> Result<usize> {
         // CSV data
         // - id             show_id
@@ -136,31 +370,48 @@ impl Db {
         // - imdb_rating    imdbRating (multiplied by 10 -> 0..100)
         // - imdb_votes     imdbVotes
         // - imdb_id        imdbID -> URL https://www.imdb.com/title/<imdb_id>
+
+        // Match, added via MIGRATIONS
+        // - omdb_title       Title
+        // - omdb_year        Year (start year for series)
+        // - match_confidence 0..100 how likely the OMDb title is the Netflix one
+        // - match_issues     year, type or title mismatch, comma separated
+        // - lookup_title     title variant OMDb found the title by
+
+        // Duration, added via MIGRATIONS
+        // - runtime_minutes  runtime of movies, of episodes for series synced with OMDb
+        // - season_count     number of seasons of series
+
+        // Added via MIGRATIONS
+        // - date_added       date_added as ISO date, i.e. 2016-09-09
+        // - weighted_rating  unused, queries compute it as `weighted` instead
         self.con.execute(CREATE_TABLE_QUERY, NO_PARAMS)
     }
 
+    pub fn create_requests_table(&self) -> Result<usize> {
+        // - api_key        OMDb API key the requests were made with
+        // - day            days since UNIX_EPOCH (UTC) the requests were made on
+        // - count          number of requests made that day
+        self.con.execute(CREATE_REQUESTS_TABLE_QUERY, NO_PARAMS)
+    }
+
+    pub fn create_presets_table(&self) -> Result<usize> {
+        // - name           name the user saved the preset under
+        // - preset         the preset as JSON, see `Preset`
+        self.con.execute(CREATE_PRESETS_TABLE_QUERY, NO_PARAMS)
+    }
+
     pub fn upsert_row(&self, row: &RatedRow) -> Result<usize> {
-        self.con.execute(
-            UPSERT_QUERY,
-            params![
-                row.id,
-                row.title,
-                row.year,
-                row.cast,
-                row.country,
-                row.director,
-                row.typ,
-                row.duration,
-                row.plot,
-                row.genre,
-                row.writer,
-                row.language,
-                row.imdb_rating,
-                row.imdb_votes,
-                row.imdb_id,
-                row.last_sync
-            ],
-        )
+        let mut stmt = self.con.prepare_cached(UPSERT_QUERY)?;
+        execute_row(&mut stmt, row)
+    }
+
+    /// Inserts all rows in one transaction, reporting `(inserted, total)` after every batch.
+    pub fn upsert_rows<F>(&self, rows: &[RatedRow], on_progress: F) -> Result<(), Box<dyn StdError>>
+    where
+        F: FnMut(usize, usize),
+    {
+        self.write_rows(UPSERT_QUERY, rows, on_progress)
     }
 
     pub fn get_unsynced_rows(&self) -> Result<Vec<RatedRow>, Error> {
@@ -175,40 +426,72 @@ impl Db {
         iter.collect()
     }
 
+    /// Synced rows whose last sync was before the given time, the ones synced longest ago first.
+    pub fn get_stale_rows(&self, synced_before: i64) -> Result<Vec<RatedRow>, Error> {
+        let mut stmt = self.con.prepare(SELECT_STALE_QUERY)?;
+        let iter = stmt.query_map(params![synced_before], |row| Ok(rated_row_from_row(row)))?;
+        iter.collect()
+    }
+
+    /// When the title synced longest ago and the one synced most recently were synced.
+    pub fn get_sync_range(&self) -> Result<(Option<i64>, Option<i64>)> {
+        self.con
+            .query_row(SELECT_SYNC_RANGE_QUERY, NO_PARAMS, |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+    }
+
     pub fn get_synced_rows_sorted(&self) -> Result<Vec<RatedRow>, Error> {
         let mut stmt = self.con.prepare(SELECT_SYNCED_SORTED_BY_RATING_QUERY)?;
         let iter = stmt.query_map(NO_PARAMS, |row| Ok(rated_row_from_row(&row)))?;
         iter.collect()
     }
 
-    pub fn get_no_params_query_result(&self, query: &str) -> Result<Vec<RatedRow>, Error> {
-        let mut stmt = self.con.prepare(query)?;
-        let iter = stmt.query_map(NO_PARAMS, |row| Ok(rated_row_from_row(&row)))?;
+    pub fn get_query_result(&self, query: &Query) -> Result<Vec<RatedRow>, Error> {
+        let mut stmt = self.con.prepare(&query.sql)?;
+        let iter = stmt.query_map(&query.params, |row| Ok(rated_row_from_row(row)))?;
         iter.collect()
     }
 
     pub fn sync_row(&self, row: &RatedRow) -> Result<usize> {
-        self.con.execute(
-            SYNC_QUERY,
-            params![
-                row.id,
-                row.title,
-                row.year,
-                row.cast,
-                row.country,
-                row.director,
-                row.typ,
-                row.duration,
-                row.plot,
-                row.genre,
-                row.writer,
-                row.language,
-                row.imdb_rating,
-                row.imdb_votes,
-                row.imdb_id,
-                row.last_sync
-            ],
-        )
+        let mut stmt = self.con.prepare_cached(SYNC_QUERY)?;
+        execute_row(&mut stmt, row)
+    }
+
+    /// Updates all rows in one transaction, reporting `(synced, total)` after every batch.
+    pub fn sync_rows<F>(&self, rows: &[RatedRow], on_progress: F) -> Result<(), Box<dyn StdError>>
+    where
+        F: FnMut(usize, usize),
+    {
+        self.write_rows(SYNC_QUERY, rows, on_progress)
+    }
+
+    fn write_rows<F>(
+        &self,
+        query: &str,
+        rows: &[RatedRow],
+        mut on_progress: F,
+    ) -> Result<(), Box<dyn StdError>>
+    where
+        F: FnMut(usize, usize),
+    {
+        self.in_transaction(|| {
+            let mut stmt = self.con.prepare_cached(query)?;
+            let mut written = 0;
+            for batch in rows.chunks(PROGRESS_BATCH_SIZE) {
+                for row in batch {
+                    execute_row(&mut stmt, row)?;
+                }
+                written += batch.len();
+                on_progress(written, rows.len());
+            }
+            Ok(())
+        })
+    }
+
+    /// Marks the row as synced without changing its data.
+    pub fn touch_row(&self, id: u32, last_sync: i64) -> Result<usize> {
+        self.con.execute(TOUCH_ROW_QUERY, params![id, last_sync])
     }
 
     pub fn delete_row(&self, id: u32) -> Result<usize> {
@@ -220,4 +503,59 @@ impl Db {
         let iter = stmt.query_map(NO_PARAMS, |row| Ok(rated_row_from_row(&row)))?;
         iter.collect()
     }
+
+    pub fn get_requests_made(&self, api_key: &str, day: u32) -> Result<u32> {
+        let mut stmt = self.con.prepare(SELECT_REQUESTS_QUERY)?;
+        let mut rows = stmt.query(params![api_key, day])?;
+        match rows.next()? {
+            Some(row) => row.get(0),
+            None => Ok(0),
+        }
+    }
+
+    pub fn add_requests_made(&self, api_key: &str, day: u32, count: u32) -> Result<usize> {
+        self.con
+            .execute(ADD_REQUESTS_QUERY, params![api_key, day, count])
+    }
+
+    /// Marks at least `limit` requests as made, i.e. when OMDb told us we ran out early.
+    pub fn exhaust_requests(&self, api_key: &str, day: u32, limit: u32) -> Result<usize> {
+        self.con
+            .execute(EXHAUST_REQUESTS_QUERY, params![api_key, day, limit])
+    }
+
+    /// Saves the preset under its name, replacing the one saved under it before.
+    pub fn save_preset(&self, preset: &Preset) -> Result<usize, Box<dyn StdError>> {
+        let json = serde_json::to_string(preset)?;
+        Ok(self
+            .con
+            .execute(UPSERT_PRESET_QUERY, params![preset.name, json])?)
+    }
+
+    pub fn get_presets(&self) -> Result<Vec<Preset>, Box<dyn StdError>> {
+        let mut stmt = self.con.prepare(SELECT_PRESETS_QUERY)?;
+        let iter = stmt.query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let mut presets = vec![];
+        for entry in iter {
+            let (name, json): (String, String) = entry?;
+            presets.push(preset_from_json(name, &json)?);
+        }
+        Ok(presets)
+    }
+
+    pub fn get_preset(&self, name: &str) -> Result<Option<Preset>, Box<dyn StdError>> {
+        let mut stmt = self.con.prepare(SELECT_PRESET_QUERY)?;
+        let mut rows = stmt.query(params![name])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(preset_from_json(
+                row.get(0)?,
+                &row.get::<_, String>(1)?,
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn delete_preset(&self, name: &str) -> Result<usize> {
+        self.con.execute(DELETE_PRESET_QUERY, params![name])
+    }
 }
@@ -0,0 +1,55 @@
+use std::collections::HashSet;
+
+use super::{fold, CAST_COLUMN, TITLE_COLUMN};
+
+/// Columns whose terms match fuzzily in fuzzy mode, the others always need the exact text.
+pub const FUZZY_COLUMNS: &[&str] = &[TITLE_COLUMN, CAST_COLUMN];
+
+/// How many of the term's trigrams a value needs to have to match it in fuzzy mode.
+pub const FUZZY_THRESHOLD: f64 = 0.5;
+
+/// Trigrams of each word padded like `pg_trgm` does, i.e. "  g", " go", "god", ... "er ",
+/// ignoring case and accents.
+fn trigrams(s: &str) -> HashSet<String> {
+    let folded = fold(s);
+    let mut trigrams = HashSet::new();
+    for word in folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let chars: Vec<char> = format!("  {} ", word).chars().collect();
+        trigrams.extend(
+            chars
+                .windows(3)
+                .map(|window| window.iter().collect::<String>()),
+        );
+    }
+    trigrams
+}
+
+/// The share of the term's trigrams that are part of the value, so 1.0 if the value contains
+/// all words of the term and less the more typos the term has, i.e. "godfater" still scores
+/// 0.78 against "The Godfather".
+pub fn fuzzy_score(term: &str, value: &str) -> f64 {
+    let term = trigrams(term);
+    if term.is_empty() {
+        return 0.0;
+    }
+    let value = trigrams(value);
+    let shared = term.intersection(&value).count();
+    shared as f64 / term.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typos_and_accents() {
+        assert!(fuzzy_score("godfater", "The Godfather") > FUZZY_THRESHOLD);
+        assert_eq!(fuzzy_score("amelie", "Amélie"), 1.0);
+        assert_eq!(fuzzy_score("ship", "Battleship"), 0.6);
+        assert!(fuzzy_score("godfater", "Good Fat Cats") < FUZZY_THRESHOLD);
+        assert_eq!(fuzzy_score(" ", "The Godfather"), 0.0);
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+
+use super::{normalize_title, normalize_type};
+
+/// Matches below this confidence are considered suspicious even without a specific issue.
+pub const SUSPICIOUS_CONFIDENCE: u32 = 60;
+
+// Below this the titles are too different to be the same show
+const MIN_TITLE_SIMILARITY: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchIssue {
+    Year,
+    Type,
+    Title,
+}
+
+impl MatchIssue {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MatchIssue::Year => "year",
+            MatchIssue::Type => "type",
+            MatchIssue::Title => "title",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MatchQuality {
+    /// 0..100, how confident we are that the OMDb title is the Netflix one.
+    pub confidence: u32,
+    pub issues: Vec<MatchIssue>,
+}
+
+impl MatchQuality {
+    /// The issues as stored in the database, i.e. "year,type", `None` if there are none.
+    pub fn issues_column(&self) -> Option<String> {
+        if self.issues.is_empty() {
+            None
+        } else {
+            let issues: Vec<&str> = self.issues.iter().map(|issue| issue.as_str()).collect();
+            Some(issues.join(","))
+        }
+    }
+}
+
+fn bigrams(title: &str) -> HashSet<(char, char)> {
+    let chars: Vec<char> = normalize_title(title).chars().collect();
+    chars.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+/// Dice coefficient of the character bigrams of both titles, 1.0 for titles that are the same
+/// once normalized.
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    if normalize_title(a) == normalize_title(b) {
+        return 1.0;
+    }
+    let (a, b) = (bigrams(a), bigrams(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let shared = a.intersection(&b).count();
+    2.0 * shared as f64 / (a.len() + b.len()) as f64
+}
+
+/// Parses OMDb years like "2015", "2019–2020" or "2019–" into start and end year.
+pub fn parse_omdb_year(year: &str) -> (Option<u32>, Option<u32>) {
+    let mut years = year
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|year| year.parse::<u32>().ok());
+    let start = years.next().flatten();
+    let end = years.next().flatten();
+    (start, end)
+}
+
+fn year_matches(netflix_year: u32, typ: &str, omdb_year: &str) -> bool {
+    match (typ, parse_omdb_year(omdb_year)) {
+        (_, (None, _)) => false,
+        // Netflix lists series with the release year of their latest season
+        ("series", (Some(start), end)) => {
+            let end = end.unwrap_or(netflix_year);
+            start <= netflix_year + 1 && netflix_year <= end + 1
+        }
+        // Netflix sometimes lists the year the movie came out in a country
+        (_, (Some(start), _)) => (start as i64 - netflix_year as i64).abs() <= 1,
+    }
+}
+
+/// Compares what Netflix lists with what OMDb returned for it.
+/// The OMDb title is scored against the closest of `netflix_titles`, i.e. the Netflix title and
+/// the variant of it that OMDb found the match with.
+pub fn assess_match(
+    netflix_titles: &[&str],
+    netflix_year: u32,
+    netflix_type: &str,
+    omdb_title: &str,
+    omdb_year: &str,
+    omdb_type: &str,
+) -> MatchQuality {
+    let similarity = netflix_titles
+        .iter()
+        .map(|netflix_title| title_similarity(netflix_title, omdb_title))
+        .fold(0.0, f64::max);
+    let typ = normalize_type(netflix_type);
+
+    let mut issues = vec![];
+    let mut confidence = (similarity * 100.0).round() as i32;
+    if !year_matches(netflix_year, typ, omdb_year) {
+        issues.push(MatchIssue::Year);
+        confidence -= 30;
+    }
+    if typ != normalize_type(omdb_type) {
+        issues.push(MatchIssue::Type);
+        confidence -= 30;
+    }
+    if similarity < MIN_TITLE_SIMILARITY {
+        issues.push(MatchIssue::Title);
+    }
+
+    MatchQuality {
+        confidence: confidence.max(0) as u32,
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn omdb_years() {
+        assert_eq!(parse_omdb_year("2015"), (Some(2015), None));
+        assert_eq!(parse_omdb_year("2019–2020"), (Some(2019), Some(2020)));
+        assert_eq!(parse_omdb_year("2019–"), (Some(2019), None));
+        assert_eq!(parse_omdb_year("N/A"), (None, None));
+    }
+
+    #[test]
+    fn exact_match() {
+        let quality = assess_match(
+            &["Amélie & Co."],
+            2001,
+            "Movie",
+            "amélie and co",
+            "2001",
+            "movie",
+        );
+        assert_eq!(
+            quality,
+            MatchQuality {
+                confidence: 100,
+                issues: vec![]
+            }
+        );
+        assert_eq!(quality.issues_column(), None);
+    }
+
+    #[test]
+    fn series_listed_with_latest_season() {
+        let quality = assess_match(&["Ozark"], 2020, "TV Show", "Ozark", "2017–2022", "series");
+        assert!(quality.issues.is_empty());
+    }
+
+    #[test]
+    fn matched_by_title_variant() {
+        let quality = assess_match(
+            &["Narcos: Season 2", "Narcos"],
+            2016,
+            "TV Show",
+            "Narcos",
+            "2015–2017",
+            "series",
+        );
+        assert_eq!(
+            quality,
+            MatchQuality {
+                confidence: 100,
+                issues: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn wrong_match() {
+        let quality = assess_match(
+            &["Carrie"],
+            2013,
+            "Movie",
+            "Sex and the City",
+            "1998–2004",
+            "series",
+        );
+        assert_eq!(
+            quality.issues,
+            vec![MatchIssue::Year, MatchIssue::Type, MatchIssue::Title]
+        );
+        assert_eq!(quality.confidence, 0);
+        assert_eq!(quality.issues_column(), Some("year,type,title".to_string()));
+    }
+}
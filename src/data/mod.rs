@@ -1,11 +1,25 @@
+pub use self::any_field::*;
 pub use self::column_filter::*;
 pub use self::data_types::*;
 pub use self::db::Db;
+pub use self::fuzzy::*;
+pub use self::match_quality::*;
+pub use self::preset::*;
 pub use self::query_builder::*;
+pub use self::query_parser::*;
+pub use self::range_filter::*;
+pub use self::sort::*;
 pub use self::util::*;
 
+mod any_field;
 mod column_filter;
 mod data_types;
 mod db;
+mod fuzzy;
+mod match_quality;
+mod preset;
 mod query_builder;
+mod query_parser;
+mod range_filter;
+mod sort;
 mod util;
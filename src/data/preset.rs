@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{ColumnFilter, ItemType, QuerySyntaxError, RangeFilter, Sort, RANGE_COLUMNS};
+
+/// Filters, type of show and sort saved under a name so that they can be loaded again.
+/// It is stored as JSON, settings missing from older presets take their defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Preset {
+    #[serde(skip)]
+    pub name: String,
+    /// Queries by column, i.e. "genre", empty ones are left out.
+    pub filters: BTreeMap<String, String>,
+    /// Ranges by column, i.e. "imdb_votes".
+    pub ranges: BTreeMap<String, String>,
+    pub item_type: ItemType,
+    pub sort: Sort,
+    pub fuzzy: bool,
+}
+
+impl Preset {
+    pub fn column_filters(&self) -> Vec<ColumnFilter> {
+        self.filters
+            .iter()
+            .map(|(column, query)| ColumnFilter::from((&column[..], query)).fuzzy(self.fuzzy))
+            .collect()
+    }
+
+    pub fn range_filters(&self) -> Result<Vec<RangeFilter>, QuerySyntaxError> {
+        let mut filters = vec![];
+        for column in RANGE_COLUMNS {
+            if let Some(range) = self.ranges.get(column.name()) {
+                filters.extend(RangeFilter::parse(*column, range)?);
+            }
+        }
+        Ok(filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{SortKey, GENRE_COLUMN};
+
+    #[test]
+    fn json_round_trip() {
+        let mut preset = Preset {
+            item_type: ItemType::Movie,
+            sort: Sort::new(SortKey::Votes),
+            fuzzy: true,
+            ..Default::default()
+        };
+        preset
+            .filters
+            .insert(GENRE_COLUMN.to_string(), "thriller".to_string());
+        preset
+            .ranges
+            .insert("imdb_votes".to_string(), "10k-".to_string());
+        let json = serde_json::to_string(&preset).unwrap();
+        assert_eq!(
+            json,
+            r#"{"filters":{"genre":"thriller"},"ranges":{"imdb_votes":"10k-"},"item_type":"movie","sort":"votes:desc","fuzzy":true}"#
+        );
+        assert_eq!(serde_json::from_str::<Preset>(&json).unwrap(), preset);
+        assert_eq!(
+            serde_json::from_str::<Preset>(r#"{"filters":{"genre":"thriller"}}"#)
+                .unwrap()
+                .item_type,
+            ItemType::Both
+        );
+    }
+}
@@ -1,7 +1,15 @@
-use super::ColumnFilter;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
-const QUERY_HEAD: &str = "SELECT * FROM nf_imdb WHERE";
-const QUERY_TAIL: &str = "last_sync IS NOT NULL ORDER BY imdb_rating DESC;";
+use super::{ColumnFilter, QuerySyntaxError, RangeFilter, Sort, SUSPICIOUS_CONFIDENCE};
+
+// The mean rating that ratings with few votes are pulled towards in the weighted score
+const MEAN_RATING_SQL: &str = "(SELECT AVG(imdb_rating) FROM nf_imdb WHERE last_sync IS NOT NULL)";
+const QUERY_TAIL: &str = "last_sync IS NOT NULL ORDER BY";
+
+const SYNC_QUERY_HEAD: &str = "SELECT * FROM nf_imdb";
+// Unsynced titles sort first since NULL is less than any value
+const SYNC_QUERY_TAIL: &str = "ORDER BY last_sync ASC;";
 
 pub const GENRE_COLUMN: &str = "genre";
 pub const TITLE_COLUMN: &str = "title";
@@ -10,16 +18,57 @@ pub const LANGUAGE_COLUMN: &str = "language";
 pub const COUNTRY_COLUMN: &str = "country";
 pub const DIRECTOR_COLUMN: &str = "director";
 pub const PLOT_COLUMN: &str = "plot";
+pub const WRITER_COLUMN: &str = "writer";
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
 pub enum ItemType {
     Movie,
     Series,
     Both,
 }
+
+// Deriving it via `#[default]` needs Rust 1.62
+#[allow(clippy::derivable_impls)]
+impl Default for ItemType {
+    fn default() -> Self {
+        ItemType::Both
+    }
+}
+
+impl FromStr for ItemType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "movie" | "movies" => Ok(ItemType::Movie),
+            "series" => Ok(ItemType::Series),
+            "both" => Ok(ItemType::Both),
+            _ => Err(format!("Unknown item type '{}'", s)),
+        }
+    }
+}
 const MOVIE_ITEM_FILTER: &str = "\n  AND type = 'movie'";
 const SHOW_ITEM_FILTER: &str = "\n  AND type = 'series'";
 const BOTH_ITEM_FILTER: &str = "";
 
+/// SQL, or a condition that is part of it, with `?` placeholders and the values to bind to them
+/// in order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    pub sql: String,
+    pub params: Vec<String>,
+}
+
+impl From<String> for Query {
+    fn from(sql: String) -> Self {
+        Self {
+            sql,
+            params: vec![],
+        }
+    }
+}
+
 fn get_item_filter(item_type: &ItemType) -> String {
     match item_type {
         ItemType::Movie => MOVIE_ITEM_FILTER.to_string(),
@@ -28,92 +77,273 @@ fn get_item_filter(item_type: &ItemType) -> String {
     }
 }
 
-pub fn build_sorted_query(item_type: &ItemType) -> String {
+/// Selects all columns and the rating weighed like IMDb's Top 250 as `weighted`,
+/// `(v * R + m * C) / (v + m)` with the title's votes `v` and rating `R`, the mean rating `C` of
+/// all synced titles and `prior_votes` as `m`. The fewer votes a title has, the closer its score
+/// stays to the mean.
+///
+/// Computed when queried since every sync changes the mean, the prior is a number and thus safe
+/// to inline.
+fn query_head(prior_votes: u32) -> String {
+    format!(
+        "SELECT *, ROUND((imdb_votes * imdb_rating + {0} * {1}) / (imdb_votes + {0}), 2) AS weighted\n\
+         FROM nf_imdb WHERE",
+        prior_votes, MEAN_RATING_SQL
+    )
+}
+
+/// The conditions of all filters with their params in the order they appear in.
+fn filter_conditions(
+    filters: Vec<ColumnFilter>,
+    ranges: &[RangeFilter],
+) -> Result<(Vec<String>, Vec<String>), QuerySyntaxError> {
+    let mut conditions = vec![];
+    let mut params = vec![];
+    for filter in filters {
+        for condition in filter.sql_conditions()? {
+            conditions.push(condition.sql);
+            params.extend(condition.params);
+        }
+    }
+    for range in ranges {
+        let condition = range.sql_condition();
+        conditions.push(condition.sql);
+        params.extend(condition.params);
+    }
+    Ok((conditions, params))
+}
+
+pub fn build_sorted_query(item_type: &ItemType, sort: &Sort, prior_votes: u32) -> Query {
     let item_filter = get_item_filter(item_type).replace(" AND ", "");
     let and = match item_type {
         ItemType::Movie | ItemType::Series => "AND ",
         ItemType::Both => "",
     };
-    format!("{}{}\n {}{}", QUERY_HEAD, item_filter, and, QUERY_TAIL)
+    format!(
+        "{}{}\n {}{} {};",
+        query_head(prior_votes),
+        item_filter,
+        and,
+        QUERY_TAIL,
+        sort.order_by()
+    )
+    .into()
 }
 
-pub fn build_sorted_filtered_query(filters: Vec<ColumnFilter>, item_type: &ItemType) -> String {
-    let mut matched_before = false;
-    let resolved_filters: Vec<String> = filters
-        .iter()
-        .flat_map(|filter| {
-            let matches = filter.sql_fragments(matched_before);
-            matched_before = matched_before || matches.len() > 0;
-            matches
-        })
-        .collect();
-
-    let query_filter = resolved_filters.join("");
+pub fn build_sorted_filtered_query(
+    filters: Vec<ColumnFilter>,
+    ranges: &[RangeFilter],
+    item_type: &ItemType,
+    sort: &Sort,
+    prior_votes: u32,
+) -> Result<Query, QuerySyntaxError> {
+    let mut ranks = vec![];
+    for filter in &filters {
+        ranks.extend(filter.fuzzy_rank()?);
+    }
+    let (conditions, mut params) = filter_conditions(filters, ranges)?;
+    if conditions.is_empty() {
+        return Ok(build_sorted_query(item_type, sort, prior_votes));
+    }
     let item_filter = get_item_filter(item_type);
 
-    format!(
-        "{}{}{}\n  AND {}",
-        QUERY_HEAD, query_filter, item_filter, QUERY_TAIL
-    )
+    // Fuzzy matches are listed the closest first, the sort only orders equally close ones
+    let mut order_by = vec![];
+    if !ranks.is_empty() {
+        let scores: Vec<String> = ranks.iter().map(|rank| rank.sql.clone()).collect();
+        order_by.push(format!("{} DESC", scores.join(" + ")));
+        params.extend(ranks.into_iter().flat_map(|rank| rank.params));
+    }
+    order_by.push(sort.order_by());
+
+    let sql = format!(
+        "{}\n  {}{}\n  AND {} {};",
+        query_head(prior_votes),
+        conditions.join("\n  AND "),
+        item_filter,
+        QUERY_TAIL,
+        order_by.join(", ")
+    );
+    Ok(Query { sql, params })
+}
+
+/// Selects synced titles whose match with OMDb looks wrong, the least likely ones first.
+pub fn build_suspicious_query(
+    filters: Vec<ColumnFilter>,
+    ranges: &[RangeFilter],
+    item_type: &ItemType,
+    prior_votes: u32,
+) -> Result<Query, QuerySyntaxError> {
+    let (mut conditions, params) = filter_conditions(filters, ranges)?;
+    match item_type {
+        ItemType::Movie => conditions.push("type = 'movie'".to_string()),
+        ItemType::Series => conditions.push("type = 'series'".to_string()),
+        ItemType::Both => {}
+    };
+    conditions.push(format!(
+        "(match_issues IS NOT NULL OR match_confidence < {})",
+        SUSPICIOUS_CONFIDENCE
+    ));
+    conditions.push("last_sync IS NOT NULL".to_string());
+
+    let sql = format!(
+        "{}\n  {}\n ORDER BY match_confidence ASC;",
+        query_head(prior_votes),
+        conditions.join("\n  AND ")
+    );
+    Ok(Query { sql, params })
+}
+
+/// Selects titles to sync whether they were synced before or not, the ones synced longest ago
+/// first. Unsynced titles still have the type from the Netflix export, i.e. 'Movie' or 'TV Show'.
+pub fn build_sync_query(
+    ids: &[u32],
+    filters: Vec<ColumnFilter>,
+    item_type: &ItemType,
+) -> Result<Query, QuerySyntaxError> {
+    let mut conditions: Vec<String> = vec![];
+    if !ids.is_empty() {
+        let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        conditions.push(format!("id IN ({})", ids.join(", ")));
+    }
+    let (filter_conditions, params) = filter_conditions(filters, &[])?;
+    conditions.extend(filter_conditions);
+    match item_type {
+        ItemType::Movie => conditions.push("lower(type) = 'movie'".to_string()),
+        ItemType::Series => conditions.push("lower(type) IN ('series', 'tv show')".to_string()),
+        ItemType::Both => {}
+    };
+
+    let where_clause = if conditions.is_empty() {
+        "".to_string()
+    } else {
+        format!(" WHERE\n  {}", conditions.join("\n  AND "))
+    };
+    let sql = format!("{}{}\n {}", SYNC_QUERY_HEAD, where_clause, SYNC_QUERY_TAIL);
+    Ok(Query { sql, params })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::data::{RangeColumn, SortDirection, SortKey, DEFAULT_PRIOR_VOTES};
+
+    fn query(sql: &str, params: &[&str]) -> Query {
+        Query {
+            sql: sql.to_string(),
+            params: params.iter().map(|param| param.to_string()).collect(),
+        }
+    }
+
+    // Queries listing titles, which all select the same columns
+    fn listing(sql: &str, params: &[&str]) -> Query {
+        query(
+            &format!("{}{}", query_head(DEFAULT_PRIOR_VOTES), sql),
+            params,
+        )
+    }
+
     #[test]
     fn query_genre_sci_not_adventure_drama() {
         assert_eq!(
             build_sorted_filtered_query(
                 vec![(GENRE_COLUMN, "sci !adventure drama").into()],
-                &ItemType::Both
-            ),
-            "SELECT * FROM nf_imdb WHERE
-  genre LIKE '%sci%'
-  AND NOT genre LIKE '%adventure%'
-  AND genre LIKE '%drama%'
-  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC;"
+                &[],
+                &ItemType::Both,
+                &Sort::default(),
+                DEFAULT_PRIOR_VOTES
+            )
+            .unwrap(),
+            listing(
+                "
+  instr(fold(genre), ?) > 0
+  AND NOT instr(fold(genre), ?) > 0
+  AND instr(fold(genre), ?) > 0
+  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC, imdb_votes DESC, title ASC;",
+                &["sci", "adventure", "drama"]
+            )
         )
     }
 
     #[test]
     fn query_title_ship() {
         assert_eq!(
-            build_sorted_filtered_query(vec![(TITLE_COLUMN, "ship").into()], &ItemType::Both),
-            "SELECT * FROM nf_imdb WHERE
-  title LIKE '%ship%'
-  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC;"
+            build_sorted_filtered_query(
+                vec![(TITLE_COLUMN, "ship").into()],
+                &[],
+                &ItemType::Both,
+                &Sort::default(),
+                DEFAULT_PRIOR_VOTES
+            )
+            .unwrap(),
+            listing(
+                "
+  instr(fold(title), ?) > 0
+  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC, imdb_votes DESC, title ASC;",
+                &["ship"]
+            )
         )
     }
 
     #[test]
     fn query_country_not_india() {
         assert_eq!(
-            build_sorted_filtered_query(vec![(COUNTRY_COLUMN, "!india").into()], &ItemType::Both),
-            "SELECT * FROM nf_imdb WHERE
-  NOT country LIKE '%india%'
-  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC;"
+            build_sorted_filtered_query(
+                vec![(COUNTRY_COLUMN, "!india").into()],
+                &[],
+                &ItemType::Both,
+                &Sort::default(),
+                DEFAULT_PRIOR_VOTES
+            )
+            .unwrap(),
+            listing(
+                "
+  NOT instr(fold(country), ?) > 0
+  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC, imdb_votes DESC, title ASC;",
+                &["india"]
+            )
         )
     }
 
     #[test]
     fn query_title_ship_movies_only() {
         assert_eq!(
-            build_sorted_filtered_query(vec![(TITLE_COLUMN, "ship").into()], &ItemType::Movie),
-            "SELECT * FROM nf_imdb WHERE
-  title LIKE '%ship%'
+            build_sorted_filtered_query(
+                vec![(TITLE_COLUMN, "ship").into()],
+                &[],
+                &ItemType::Movie,
+                &Sort::default(),
+                DEFAULT_PRIOR_VOTES
+            )
+            .unwrap(),
+            listing(
+                "
+  instr(fold(title), ?) > 0
   AND type = 'movie'
-  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC;"
+  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC, imdb_votes DESC, title ASC;",
+                &["ship"]
+            )
         )
     }
 
     #[test]
     fn query_title_ship_series_only() {
         assert_eq!(
-            build_sorted_filtered_query(vec![(TITLE_COLUMN, "ship").into()], &ItemType::Series),
-            "SELECT * FROM nf_imdb WHERE
-  title LIKE '%ship%'
+            build_sorted_filtered_query(
+                vec![(TITLE_COLUMN, "ship").into()],
+                &[],
+                &ItemType::Series,
+                &Sort::default(),
+                DEFAULT_PRIOR_VOTES
+            )
+            .unwrap(),
+            listing(
+                "
+  instr(fold(title), ?) > 0
   AND type = 'series'
-  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC;"
+  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC, imdb_votes DESC, title ASC;",
+                &["ship"]
+            )
         )
     }
 
@@ -122,12 +352,19 @@ mod tests {
         assert_eq!(
             build_sorted_filtered_query(
                 vec![(TITLE_COLUMN, "ship").into(), (GENRE_COLUMN, "sci").into()],
-                &ItemType::Both
-            ),
-            "SELECT * FROM nf_imdb WHERE
-  title LIKE '%ship%'
-  AND genre LIKE '%sci%'
-  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC;"
+                &[],
+                &ItemType::Both,
+                &Sort::default(),
+                DEFAULT_PRIOR_VOTES
+            )
+            .unwrap(),
+            listing(
+                "
+  instr(fold(title), ?) > 0
+  AND instr(fold(genre), ?) > 0
+  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC, imdb_votes DESC, title ASC;",
+                &["ship", "sci"]
+            )
         )
     }
 
@@ -140,13 +377,166 @@ mod tests {
                     (GENRE_COLUMN, "sci").into(),
                     (CAST_COLUMN, "!badactor").into(),
                 ],
-                &ItemType::Both
-            ),
-            "SELECT * FROM nf_imdb WHERE
-  title LIKE '%ship%'
-  AND genre LIKE '%sci%'
-  AND NOT `cast` LIKE '%badactor%'
-  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC;"
+                &[],
+                &ItemType::Both,
+                &Sort::default(),
+                DEFAULT_PRIOR_VOTES
+            )
+            .unwrap(),
+            listing(
+                "
+  instr(fold(title), ?) > 0
+  AND instr(fold(genre), ?) > 0
+  AND NOT instr(fold(`cast`), ?) > 0
+  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC, imdb_votes DESC, title ASC;",
+                &["ship", "sci", "badactor"]
+            )
+        )
+    }
+
+    #[test]
+    fn query_genre_drama_min_rating_and_votes() {
+        let ranges = vec![
+            RangeFilter {
+                column: RangeColumn::Rating,
+                min: Some(75),
+                max: None,
+            },
+            RangeFilter {
+                column: RangeColumn::Votes,
+                min: Some(10000),
+                max: None,
+            },
+        ];
+        assert_eq!(
+            build_sorted_filtered_query(
+                vec![(GENRE_COLUMN, "drama").into()],
+                &ranges,
+                &ItemType::Both,
+                &Sort::default(),
+                DEFAULT_PRIOR_VOTES
+            )
+            .unwrap(),
+            listing(
+                "
+  instr(fold(genre), ?) > 0
+  AND imdb_rating >= 75
+  AND imdb_votes >= 10000
+  AND last_sync IS NOT NULL ORDER BY imdb_rating DESC, imdb_votes DESC, title ASC;",
+                &["drama"]
+            )
+        )
+    }
+
+    #[test]
+    fn query_title_fuzzy_genre_drama() {
+        assert_eq!(
+            build_sorted_filtered_query(
+                vec![
+                    ColumnFilter::from((TITLE_COLUMN, "godfater")).fuzzy(true),
+                    ColumnFilter::from((GENRE_COLUMN, "drama")).fuzzy(true),
+                ],
+                &[],
+                &ItemType::Both,
+                &Sort::default(),
+                DEFAULT_PRIOR_VOTES
+            )
+            .unwrap(),
+            listing(
+                "
+  (instr(fold(title), ?) > 0 OR fuzzy_score(?, title) >= 0.5)
+  AND instr(fold(genre), ?) > 0
+  AND last_sync IS NOT NULL ORDER BY fuzzy_score(?, title) DESC, imdb_rating DESC, imdb_votes DESC, title ASC;",
+                &["godfater", "godfater", "drama", "godfater"]
+            )
+        )
+    }
+
+    #[test]
+    fn query_series_sorted_by_votes_ascending() {
+        let sort = Sort {
+            key: SortKey::Votes,
+            direction: SortDirection::Ascending,
+        };
+        assert_eq!(
+            build_sorted_query(&ItemType::Series, &sort, DEFAULT_PRIOR_VOTES),
+            listing(
+                "
+ type = 'series'
+ AND last_sync IS NOT NULL ORDER BY imdb_votes IS NULL, imdb_votes ASC, imdb_rating DESC, title ASC;",
+                &[]
+            )
+        )
+    }
+
+    #[test]
+    fn sync_query_ids() {
+        assert_eq!(
+            build_sync_query(&[1, 42], vec![], &ItemType::Both).unwrap(),
+            query(
+                "SELECT * FROM nf_imdb WHERE
+  id IN (1, 42)
+ ORDER BY last_sync ASC;",
+                &[]
+            )
+        )
+    }
+
+    #[test]
+    fn sync_query_title_genre_series() {
+        assert_eq!(
+            build_sync_query(
+                &[],
+                vec![
+                    (TITLE_COLUMN, "ship").into(),
+                    (GENRE_COLUMN, "!comedy").into()
+                ],
+                &ItemType::Series
+            )
+            .unwrap(),
+            query(
+                "SELECT * FROM nf_imdb WHERE
+  instr(fold(title), ?) > 0
+  AND NOT instr(fold(genre), ?) > 0
+  AND lower(type) IN ('series', 'tv show')
+ ORDER BY last_sync ASC;",
+                &["ship", "comedy"]
+            )
+        )
+    }
+
+    #[test]
+    fn suspicious_query_title_movies_only() {
+        assert_eq!(
+            build_suspicious_query(
+                vec![(TITLE_COLUMN, "ship").into()],
+                &[],
+                &ItemType::Movie,
+                DEFAULT_PRIOR_VOTES
+            )
+            .unwrap(),
+            listing(
+                "
+  instr(fold(title), ?) > 0
+  AND type = 'movie'
+  AND (match_issues IS NOT NULL OR match_confidence < 60)
+  AND last_sync IS NOT NULL
+ ORDER BY match_confidence ASC;",
+                &["ship"]
+            )
+        )
+    }
+
+    #[test]
+    fn query_sorted_by_weighted_rating() {
+        assert_eq!(
+            build_sorted_query(&ItemType::Both, &Sort::new(SortKey::Weighted), 25_000),
+            query(
+                "SELECT *, ROUND((imdb_votes * imdb_rating + 25000 * (SELECT AVG(imdb_rating) FROM nf_imdb WHERE last_sync IS NOT NULL)) / (imdb_votes + 25000), 2) AS weighted
+FROM nf_imdb WHERE
+ last_sync IS NOT NULL ORDER BY weighted IS NULL, weighted DESC, imdb_rating DESC, title ASC;",
+                &[]
+            )
         )
     }
 }
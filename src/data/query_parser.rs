@@ -0,0 +1,457 @@
+use std::{error::Error, fmt};
+
+use super::{
+    fold, ANY_COLUMN, ANY_COLUMNS, CAST_COLUMN, COUNTRY_COLUMN, DIRECTOR_COLUMN, FUZZY_COLUMNS,
+    FUZZY_THRESHOLD, GENRE_COLUMN, LANGUAGE_COLUMN, PLOT_COLUMN, TITLE_COLUMN, WRITER_COLUMN,
+};
+
+/// Columns that can be searched via a `field:` prefix.
+pub const QUERY_FIELDS: &[&str] = &[
+    GENRE_COLUMN,
+    TITLE_COLUMN,
+    CAST_COLUMN,
+    COUNTRY_COLUMN,
+    LANGUAGE_COLUMN,
+    PLOT_COLUMN,
+    DIRECTOR_COLUMN,
+    WRITER_COLUMN,
+    ANY_COLUMN,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuerySyntaxError {
+    /// The column whose filter didn't parse, set once it is known.
+    pub column: Option<String>,
+    pub message: String,
+    /// Offset in chars into the query.
+    pub position: usize,
+}
+
+impl QuerySyntaxError {
+    fn new(message: &str, position: usize) -> Self {
+        Self {
+            column: None,
+            message: message.to_string(),
+            position,
+        }
+    }
+}
+
+impl fmt::Display for QuerySyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.column {
+            Some(column) => write!(
+                f,
+                "{} filter: {} at {}",
+                column,
+                self.message,
+                self.position + 1
+            ),
+            None => write!(f, "{} at {}", self.message, self.position + 1),
+        }
+    }
+}
+
+impl Error for QuerySyntaxError {}
+
+/// A parsed filter, i.e. `genre:(comedy | drama) !"stand up"`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryExpr {
+    /// Matches if the column contains the text, `field` overrides the filter's column.
+    Term {
+        field: Option<String>,
+        text: String,
+    },
+    Not(Box<QueryExpr>),
+    And(Vec<QueryExpr>),
+    Or(Vec<QueryExpr>),
+}
+
+fn quote_column(column: &str) -> String {
+    // cast is a keyword in SQL
+    if column == CAST_COLUMN {
+        "`cast`".to_string()
+    } else {
+        column.to_string()
+    }
+}
+
+impl QueryExpr {
+    /// Compiles the expression to SQL searching `column` unless a term has a field prefix,
+    /// pushing the values for its `?` placeholders onto `params`. Terms match regardless of
+    /// case and accents, see `fold`.
+    ///
+    /// If `fuzzy` is set, terms searching one of the `FUZZY_COLUMNS` also match values that are
+    /// similar enough, i.e. with a typo.
+    pub fn to_sql(&self, column: &str, fuzzy: bool, params: &mut Vec<String>) -> String {
+        let join = |exprs: &[QueryExpr], op: &str, params: &mut Vec<String>| {
+            let sqls: Vec<String> = exprs
+                .iter()
+                .map(|expr| expr.to_sql(column, fuzzy, params))
+                .collect();
+            format!("({})", sqls.join(op))
+        };
+        match self {
+            QueryExpr::Term { field, text } => {
+                let column = field.as_deref().unwrap_or(column);
+                if column == ANY_COLUMN {
+                    let sqls: Vec<String> = ANY_COLUMNS
+                        .iter()
+                        .map(|column| {
+                            params.push(fold(text));
+                            format!("instr(fold({}), ?) > 0", quote_column(column))
+                        })
+                        .collect();
+                    return format!("({})", sqls.join(" OR "));
+                }
+                params.push(fold(text));
+                // LIKE only ignores the case of ASCII letters
+                let contains = format!("instr(fold({}), ?) > 0", quote_column(column));
+                if fuzzy && FUZZY_COLUMNS.contains(&column) {
+                    params.push(text.clone());
+                    format!(
+                        "({} OR fuzzy_score(?, {}) >= {})",
+                        contains,
+                        quote_column(column),
+                        FUZZY_THRESHOLD
+                    )
+                } else {
+                    contains
+                }
+            }
+            QueryExpr::Not(expr) => format!("NOT {}", expr.to_sql(column, fuzzy, params)),
+            QueryExpr::And(exprs) => join(exprs, " AND ", params),
+            QueryExpr::Or(exprs) => join(exprs, " OR ", params),
+        }
+    }
+
+    /// The terms that aren't negated with the column each searches, `column` unless the term
+    /// has a field prefix.
+    pub fn positive_terms<'a>(&'a self, column: &'a str) -> Vec<(&'a str, &'a str)> {
+        match self {
+            QueryExpr::Term { field, text } => vec![(field.as_deref().unwrap_or(column), text)],
+            QueryExpr::Not(_) => vec![],
+            QueryExpr::And(exprs) | QueryExpr::Or(exprs) => exprs
+                .iter()
+                .flat_map(|expr| expr.positive_terms(column))
+                .collect(),
+        }
+    }
+
+    /// How well a value matches the terms that search one of the `FUZZY_COLUMNS`, summed up so
+    /// that the best matches can be listed first. Negated terms don't count.
+    pub fn fuzzy_rank_sql(&self, column: &str, params: &mut Vec<String>) -> Vec<String> {
+        self.positive_terms(column)
+            .into_iter()
+            .filter(|(column, _)| FUZZY_COLUMNS.contains(column))
+            .map(|(column, text)| {
+                params.push(text.to_string());
+                format!("fuzzy_score(?, {})", quote_column(column))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Or,
+    And,
+    Not,
+    Field(String),
+    Word(String),
+    Phrase(String),
+}
+
+fn is_word_end(c: char) -> bool {
+    c.is_whitespace() || c == '(' || c == ')' || c == '|' || c == '"'
+}
+
+fn tokenize(query: &str) -> Result<Vec<(Token, usize)>, QuerySyntaxError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = vec![];
+    let mut pos = 0;
+    while pos < chars.len() {
+        let start = pos;
+        let token = match chars[pos] {
+            c if c.is_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '|' => Token::Or,
+            '!' => Token::Not,
+            '"' => {
+                let len = chars[pos + 1..].iter().position(|&c| c == '"');
+                let len = len.ok_or_else(|| QuerySyntaxError::new("unclosed quote", start))?;
+                let phrase: String = chars[pos + 1..pos + 1 + len].iter().collect();
+                if phrase.trim().is_empty() {
+                    return Err(QuerySyntaxError::new("empty quotes", start));
+                }
+                pos += len + 1;
+                Token::Phrase(phrase)
+            }
+            _ => {
+                let mut word = String::new();
+                let mut field = None;
+                while pos < chars.len() && !is_word_end(chars[pos]) {
+                    // Titles like "Star Wars: Episode I" have colons too
+                    let name = word.to_lowercase();
+                    if chars[pos] == ':' && QUERY_FIELDS.contains(&&name[..]) {
+                        field = Some(name);
+                        break;
+                    }
+                    word.push(chars[pos]);
+                    pos += 1;
+                }
+                if let Some(field) = field {
+                    tokens.push((Token::Field(field), start));
+                    pos += 1;
+                    continue;
+                }
+                let token = match &word[..] {
+                    "OR" => Token::Or,
+                    "AND" => Token::And,
+                    _ => Token::Word(word),
+                };
+                tokens.push((token, start));
+                continue;
+            }
+        };
+        tokens.push((token, start));
+        pos += 1;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    idx: usize,
+    // Where errors at the end of the query are reported
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.idx).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.idx)
+            .map(|(_, pos)| *pos)
+            .unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.idx += 1;
+        token
+    }
+
+    fn parse_or(&mut self, field: &Option<String>) -> Result<QueryExpr, QuerySyntaxError> {
+        let mut exprs = vec![self.parse_and(field)?];
+        while let Some(Token::Or) = self.peek() {
+            self.next();
+            exprs.push(self.parse_and(field)?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            QueryExpr::Or(exprs)
+        })
+    }
+
+    fn parse_and(&mut self, field: &Option<String>) -> Result<QueryExpr, QuerySyntaxError> {
+        let mut exprs = vec![self.parse_unary(field)?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                    exprs.push(self.parse_unary(field)?);
+                }
+                Some(Token::Or) | Some(Token::RParen) | None => break,
+                Some(_) => exprs.push(self.parse_unary(field)?),
+            }
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            QueryExpr::And(exprs)
+        })
+    }
+
+    fn parse_unary(&mut self, field: &Option<String>) -> Result<QueryExpr, QuerySyntaxError> {
+        let position = self.position();
+        match self.next() {
+            Some(Token::Not) => Ok(QueryExpr::Not(Box::new(self.parse_unary(field)?))),
+            Some(Token::LParen) => {
+                let expr = self.parse_or(field)?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(QuerySyntaxError::new("missing ')'", position)),
+                }
+            }
+            Some(Token::Field(name)) => self.parse_unary(&Some(name)),
+            Some(Token::Word(text)) | Some(Token::Phrase(text)) => Ok(QueryExpr::Term {
+                field: field.clone(),
+                text,
+            }),
+            Some(Token::RParen) => Err(QuerySyntaxError::new("unmatched ')'", position)),
+            Some(Token::Or) | Some(Token::And) | None => {
+                Err(QuerySyntaxError::new("expected a term", position))
+            }
+        }
+    }
+}
+
+/// Parses a filter, `None` if it has no terms.
+///
+/// Terms separated by whitespace all have to match, `|` or `OR` match either side instead.
+/// Phrases are quoted, `!` negates and `field:` searches another column than the filter's.
+pub fn parse_query(query: &str) -> Result<Option<QueryExpr>, QuerySyntaxError> {
+    let tokens = tokenize(query)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let mut parser = Parser {
+        tokens,
+        idx: 0,
+        end: query.chars().count(),
+    };
+    let expr = parser.parse_or(&None)?;
+    match parser.peek() {
+        None => Ok(Some(expr)),
+        Some(_) => Err(QuerySyntaxError::new("unmatched ')'", parser.position())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(text: &str) -> QueryExpr {
+        QueryExpr::Term {
+            field: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(parse_query("  "), Ok(None));
+    }
+
+    #[test]
+    fn phrases_and_negation() {
+        assert_eq!(
+            parse_query("\"science fiction\" !horror"),
+            Ok(Some(QueryExpr::And(vec![
+                term("science fiction"),
+                QueryExpr::Not(Box::new(term("horror")))
+            ])))
+        );
+    }
+
+    #[test]
+    fn or_binds_looser_than_and() {
+        assert_eq!(
+            parse_query("comedy | drama romance OR crime"),
+            Ok(Some(QueryExpr::Or(vec![
+                term("comedy"),
+                QueryExpr::And(vec![term("drama"), term("romance")]),
+                term("crime"),
+            ])))
+        );
+    }
+
+    #[test]
+    fn fields_and_groups() {
+        let mut params = vec![];
+        let expr = parse_query("!(comedy | drama) cast:(hanks | \"meg ryan\")")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            expr.to_sql(GENRE_COLUMN, false, &mut params),
+            "(NOT (instr(fold(genre), ?) > 0 OR instr(fold(genre), ?) > 0) AND (instr(fold(`cast`), ?) > 0 OR instr(fold(`cast`), ?) > 0))"
+        );
+        assert_eq!(params, vec!["comedy", "drama", "hanks", "meg ryan"]);
+    }
+
+    #[test]
+    fn folded_terms() {
+        let mut params = vec![];
+        let expr = parse_query("Pokémon").unwrap().unwrap();
+        assert_eq!(
+            expr.to_sql(TITLE_COLUMN, false, &mut params),
+            "instr(fold(title), ?) > 0"
+        );
+        assert_eq!(params, vec!["pokemon"]);
+    }
+
+    #[test]
+    fn any_field() {
+        let mut params = vec![];
+        let expr = parse_query("genre:crime any:nolan").unwrap().unwrap();
+        assert_eq!(
+            expr.to_sql(TITLE_COLUMN, false, &mut params),
+            "(instr(fold(genre), ?) > 0 AND (instr(fold(title), ?) > 0 OR instr(fold(genre), ?) > 0 \
+             OR instr(fold(`cast`), ?) > 0 OR instr(fold(director), ?) > 0 \
+             OR instr(fold(writer), ?) > 0 OR instr(fold(country), ?) > 0 \
+             OR instr(fold(language), ?) > 0 OR instr(fold(plot), ?) > 0))"
+        );
+        assert_eq!(params.len(), 9);
+    }
+
+    #[test]
+    fn fuzzy_terms() {
+        let mut params = vec![];
+        let expr = parse_query("godfater genre:crime").unwrap().unwrap();
+        assert_eq!(
+            expr.to_sql(TITLE_COLUMN, true, &mut params),
+            "((instr(fold(title), ?) > 0 OR fuzzy_score(?, title) >= 0.5) AND instr(fold(genre), ?) > 0)"
+        );
+        assert_eq!(params, vec!["godfater", "godfater", "crime"]);
+
+        let mut params = vec![];
+        let expr = parse_query("godfater !pacino").unwrap().unwrap();
+        assert_eq!(
+            expr.fuzzy_rank_sql(TITLE_COLUMN, &mut params),
+            vec!["fuzzy_score(?, title)"]
+        );
+        assert_eq!(params, vec!["godfater"]);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let error = |query| parse_query(query).unwrap_err();
+        assert_eq!(
+            error("\"science"),
+            QuerySyntaxError::new("unclosed quote", 0)
+        );
+        assert_eq!(
+            error("(comedy | drama"),
+            QuerySyntaxError::new("missing ')'", 0)
+        );
+        assert_eq!(error("comedy)"), QuerySyntaxError::new("unmatched ')'", 6));
+        assert_eq!(
+            error("comedy |"),
+            QuerySyntaxError::new("expected a term", 8)
+        );
+    }
+
+    #[test]
+    fn colons_in_titles() {
+        assert_eq!(
+            parse_query("star wars: episode"),
+            Ok(Some(QueryExpr::And(vec![
+                term("star"),
+                term("wars:"),
+                term("episode")
+            ])))
+        );
+        assert_eq!(parse_query("actor:hanks"), Ok(Some(term("actor:hanks"))));
+    }
+}
@@ -0,0 +1,181 @@
+use super::{Query, QuerySyntaxError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangeColumn {
+    Rating,
+    Votes,
+    Year,
+    Runtime,
+    Seasons,
+}
+
+pub const RANGE_COLUMNS: &[RangeColumn] = &[
+    RangeColumn::Rating,
+    RangeColumn::Votes,
+    RangeColumn::Year,
+    RangeColumn::Runtime,
+    RangeColumn::Seasons,
+];
+
+impl RangeColumn {
+    /// The database column, which also identifies the column in syntax errors.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RangeColumn::Rating => "imdb_rating",
+            RangeColumn::Votes => "imdb_votes",
+            RangeColumn::Year => "year",
+            RangeColumn::Runtime => "runtime_minutes",
+            RangeColumn::Seasons => "season_count",
+        }
+    }
+
+    fn example(&self) -> &'static str {
+        match self {
+            RangeColumn::Rating => "7.5",
+            RangeColumn::Votes => "10k",
+            RangeColumn::Year => "2010",
+            RangeColumn::Runtime => "100",
+            RangeColumn::Seasons => "2",
+        }
+    }
+
+    /// Parses a value as stored in the database, i.e. rating 7.5 as 75 and votes 10k as 10000.
+    fn parse_value(&self, value: &str) -> Option<u32> {
+        match self {
+            RangeColumn::Rating => value
+                .parse::<f64>()
+                .ok()
+                .filter(|rating| (0.0..=10.0).contains(rating))
+                .map(|rating| (rating * 10.0).round() as u32),
+            RangeColumn::Votes => {
+                let lower = value.to_lowercase();
+                let (digits, factor) = if let Some(digits) = lower.strip_suffix('k') {
+                    (digits, 1_000.0)
+                } else if let Some(digits) = lower.strip_suffix('m') {
+                    (digits, 1_000_000.0)
+                } else {
+                    (&lower[..], 1.0)
+                };
+                digits
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|votes| *votes >= 0.0)
+                    .map(|votes| (votes * factor).round() as u32)
+            }
+            RangeColumn::Year | RangeColumn::Runtime | RangeColumn::Seasons => {
+                value.parse::<u32>().ok()
+            }
+        }
+    }
+}
+
+/// Limits a numeric column to `min..=max`, either bound is optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeFilter {
+    pub column: RangeColumn,
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl RangeFilter {
+    /// Parses ranges like `7.5-` (at least), `-100` (at most), `2010-2020` or `2015` (exactly),
+    /// `None` if there is nothing to filter by.
+    pub fn parse(column: RangeColumn, range: &str) -> Result<Option<Self>, QuerySyntaxError> {
+        let range = range.trim();
+        if range.is_empty() {
+            return Ok(None);
+        }
+        let error = |position: usize| QuerySyntaxError {
+            column: Some(column.name().to_string()),
+            message: format!("expected a range like {}-", column.example()),
+            position,
+        };
+        let bound = |value: &str, position: usize| match value.trim() {
+            "" => Ok(None),
+            value => column
+                .parse_value(value)
+                .map(Some)
+                .ok_or_else(|| error(position)),
+        };
+
+        let (min, max) = match range.find('-') {
+            Some(idx) => (
+                bound(&range[..idx], 0)?,
+                bound(&range[idx + 1..], range[..=idx].chars().count())?,
+            ),
+            None => {
+                let value = bound(range, 0)?;
+                (value, value)
+            }
+        };
+        match (min, max) {
+            (None, None) => return Err(error(0)),
+            // Would compile to a `BETWEEN` that matches nothing
+            (Some(min), Some(max)) if min > max => {
+                return Err(QuerySyntaxError {
+                    column: Some(column.name().to_string()),
+                    message: "expected the lower bound first".to_string(),
+                    position: 0,
+                })
+            }
+            _ => {}
+        }
+        Ok(Some(Self { column, min, max }))
+    }
+
+    pub fn sql_condition(&self) -> Query {
+        // The bounds are parsed numbers and thus safe to inline
+        let column = self.column.name();
+        let sql = match (self.min, self.max) {
+            (Some(min), Some(max)) if min == max => format!("{} = {}", column, min),
+            (Some(min), Some(max)) => format!("{} BETWEEN {} AND {}", column, min, max),
+            (Some(min), None) => format!("{} >= {}", column, min),
+            (None, Some(max)) => format!("{} <= {}", column, max),
+            (None, None) => "1".to_string(),
+        };
+        sql.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sql(column: RangeColumn, range: &str) -> String {
+        RangeFilter::parse(column, range)
+            .unwrap()
+            .unwrap()
+            .sql_condition()
+            .sql
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(sql(RangeColumn::Rating, "7.5-"), "imdb_rating >= 75");
+        assert_eq!(sql(RangeColumn::Votes, " 10k - "), "imdb_votes >= 10000");
+        assert_eq!(
+            sql(RangeColumn::Year, "2010-2020"),
+            "year BETWEEN 2010 AND 2020"
+        );
+        assert_eq!(sql(RangeColumn::Year, "2015"), "year = 2015");
+        assert_eq!(sql(RangeColumn::Runtime, "-100"), "runtime_minutes <= 100");
+        assert_eq!(sql(RangeColumn::Seasons, "2-"), "season_count >= 2");
+        assert_eq!(RangeFilter::parse(RangeColumn::Year, " "), Ok(None));
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        let position = |column, range| RangeFilter::parse(column, range).unwrap_err().position;
+        assert_eq!(position(RangeColumn::Rating, "11-"), 0);
+        assert_eq!(position(RangeColumn::Year, "2010-20x"), 5);
+        assert_eq!(position(RangeColumn::Votes, "-"), 0);
+        assert_eq!(
+            RangeFilter::parse(RangeColumn::Year, "2020-2010"),
+            Err(QuerySyntaxError {
+                column: Some("year".to_string()),
+                message: "expected the lower bound first".to_string(),
+                position: 0,
+            })
+        );
+    }
+}
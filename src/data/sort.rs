@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+/// Votes a title needs before its own rating counts as much as the mean rating in the weighted
+/// score.
+pub const DEFAULT_PRIOR_VOTES: u32 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Rating,
+    Votes,
+    Year,
+    Title,
+    Runtime,
+    Added,
+    Weighted,
+}
+
+impl SortKey {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortKey::Rating => "rating",
+            SortKey::Votes => "votes",
+            SortKey::Year => "year",
+            SortKey::Title => "title",
+            SortKey::Runtime => "runtime",
+            SortKey::Added => "added",
+            SortKey::Weighted => "weighted",
+        }
+    }
+
+    /// The next key when cycling through them in the TUI.
+    pub fn next(&self) -> SortKey {
+        match self {
+            SortKey::Rating => SortKey::Weighted,
+            SortKey::Weighted => SortKey::Votes,
+            SortKey::Votes => SortKey::Year,
+            SortKey::Year => SortKey::Added,
+            SortKey::Added => SortKey::Runtime,
+            SortKey::Runtime => SortKey::Title,
+            SortKey::Title => SortKey::Rating,
+        }
+    }
+
+    /// Titles sort A to Z, everything else the highest first.
+    pub fn default_direction(&self) -> SortDirection {
+        match self {
+            SortKey::Title => SortDirection::Ascending,
+            _ => SortDirection::Descending,
+        }
+    }
+
+    fn sql(&self) -> &'static str {
+        match self {
+            SortKey::Rating => "imdb_rating",
+            SortKey::Votes => "imdb_votes",
+            SortKey::Year => "year",
+            SortKey::Title => "title",
+            SortKey::Runtime => "runtime_minutes",
+            SortKey::Added => "date_added",
+            // Computed by the query, see `query_head`
+            SortKey::Weighted => "weighted",
+        }
+    }
+
+    // Synced titles always have a rating and Netflix lists a title and year for all of them
+    fn is_nullable(&self) -> bool {
+        !matches!(self, SortKey::Rating | SortKey::Year | SortKey::Title)
+    }
+
+    // Breaks ties, i.e. between titles with the same rating
+    fn secondary_sql(&self) -> &'static str {
+        match self {
+            SortKey::Rating => "imdb_votes DESC, title ASC",
+            SortKey::Title => "year DESC",
+            _ => "imdb_rating DESC, title ASC",
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "rating" => Ok(SortKey::Rating),
+            "votes" => Ok(SortKey::Votes),
+            "year" => Ok(SortKey::Year),
+            "title" => Ok(SortKey::Title),
+            "runtime" => Ok(SortKey::Runtime),
+            "added" => Ok(SortKey::Added),
+            "weighted" => Ok(SortKey::Weighted),
+            _ => Err(format!("Unknown sort '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+impl SortDirection {
+    pub fn toggle(&self) -> SortDirection {
+        match self {
+            SortDirection::Ascending => SortDirection::Descending,
+            SortDirection::Descending => SortDirection::Ascending,
+        }
+    }
+
+    fn sql(&self) -> &'static str {
+        match self {
+            SortDirection::Ascending => "ASC",
+            SortDirection::Descending => "DESC",
+        }
+    }
+}
+
+/// How query results are ordered, given as `<key>` or `<key>:asc|desc`, i.e. `votes:asc`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Sort {
+    pub key: SortKey,
+    pub direction: SortDirection,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Self::new(SortKey::Rating)
+    }
+}
+
+impl Sort {
+    pub fn new(key: SortKey) -> Self {
+        Self {
+            key,
+            direction: key.default_direction(),
+        }
+    }
+
+    /// The `ORDER BY` clause without the keywords, titles without a value for the key go last.
+    pub fn order_by(&self) -> String {
+        let sql = self.key.sql();
+        let nulls_last = if self.key.is_nullable() {
+            format!("{} IS NULL, ", sql)
+        } else {
+            "".to_string()
+        };
+        format!(
+            "{}{} {}, {}",
+            nulls_last,
+            sql,
+            self.direction.sql(),
+            self.key.secondary_sql()
+        )
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = match self.direction {
+            SortDirection::Ascending => "↑",
+            SortDirection::Descending => "↓",
+        };
+        write!(f, "{} {}", self.key.name(), arrow)
+    }
+}
+
+impl FromStr for Sort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let key = parts.next().unwrap_or_default().trim().parse::<SortKey>()?;
+        let direction = match parts
+            .next()
+            .map(|direction| direction.trim().to_lowercase())
+        {
+            None => key.default_direction(),
+            Some(direction) if direction == "asc" => SortDirection::Ascending,
+            Some(direction) if direction == "desc" => SortDirection::Descending,
+            Some(direction) => return Err(format!("Unknown sort direction '{}'", direction)),
+        };
+        Ok(Self { key, direction })
+    }
+}
+
+impl From<Sort> for String {
+    fn from(sort: Sort) -> Self {
+        let direction = match sort.direction {
+            SortDirection::Ascending => "asc",
+            SortDirection::Descending => "desc",
+        };
+        format!("{}:{}", sort.key.name(), direction)
+    }
+}
+
+impl TryFrom<String> for Sort {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sorts() {
+        assert_eq!("votes".parse(), Ok(Sort::new(SortKey::Votes)));
+        assert_eq!(
+            "Title:desc".parse(),
+            Ok(Sort {
+                key: SortKey::Title,
+                direction: SortDirection::Descending
+            })
+        );
+        assert!("votes:up".parse::<Sort>().is_err());
+    }
+
+    #[test]
+    fn order_by() {
+        assert_eq!(
+            Sort::default().order_by(),
+            "imdb_rating DESC, imdb_votes DESC, title ASC"
+        );
+        let sort = Sort {
+            key: SortKey::Runtime,
+            direction: SortDirection::Ascending,
+        };
+        assert_eq!(
+            sort.order_by(),
+            "runtime_minutes IS NULL, runtime_minutes ASC, imdb_rating DESC, title ASC"
+        );
+    }
+}
@@ -1,42 +1,106 @@
 use std::{error::Error, path::PathBuf, time::SystemTime};
 
 use app_dirs::{get_app_root, AppDataType, AppInfo};
-const CREATE_SECS: u64 = 1599939357;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 
-pub fn secs_since_creation() -> u32 {
-    let since_epoch = SystemTime::now()
+/// Seconds since UNIX_EPOCH, which is how `last_sync` is stored.
+pub fn unix_now() -> i64 {
+    SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
-        .as_secs();
+        .as_secs() as i64
+}
+
+fn plural(count: i64, unit: &str) -> String {
+    if count == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", count, unit)
+    }
+}
 
-    // This hack will work for about 136 years
-    (since_epoch - CREATE_SECS) as u32
+/// How long ago the given Unix timestamp was, i.e. "3 days ago".
+pub fn format_age(timestamp: i64, now: i64) -> String {
+    let secs = (now - timestamp).max(0);
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => plural(secs / 60, "minute"),
+        3600..=86399 => plural(secs / 3600, "hour"),
+        _ => plural(secs / 86400, "day"),
+    }
 }
 
-pub enum QueryTerm {
-    And(String),
-    Not(String),
+/// Parses durations like "90 min" into minutes and "3 Seasons" into the number of seasons.
+pub fn parse_duration(duration: &str) -> (Option<u32>, Option<u32>) {
+    let mut parts = duration.split_whitespace();
+    let count = parts.next().and_then(|count| count.parse::<u32>().ok());
+    match parts.next().map(|unit| unit.to_lowercase()) {
+        Some(unit) if unit == "min" => (count, None),
+        Some(unit) if unit.starts_with("season") => (None, count),
+        _ => (None, None),
+    }
+}
+
+const MONTHS: &[&str] = &[
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// Parses the date a title was added to Netflix, i.e. "September 9, 2016", into an ISO date
+/// like "2016-09-09" which sorts chronologically.
+pub fn parse_date_added(date: &str) -> Option<String> {
+    let mut parts = date.split_whitespace();
+    let month = parts.next()?.to_lowercase();
+    let month = MONTHS.iter().position(|name| *name == month)? + 1;
+    let day = parts.next()?.trim_end_matches(',').parse::<u32>().ok()?;
+    let year = parts.next()?.parse::<u32>().ok()?;
+    Some(format!("{:04}-{:02}-{:02}", year, month, day))
 }
-pub fn destructure_query_filter(q: &str) -> Vec<QueryTerm> {
-    let terms = q.split_ascii_whitespace();
-    terms
-        .filter_map(|term| match term.chars().nth(0) {
-            Some(c) if c == '!' => {
-                let q = term.split_at(1).1;
-                if q.is_empty() {
-                    None
-                } else {
-                    Some(QueryTerm::Not(q.to_string()))
-                }
-            }
-            Some(_) => Some(QueryTerm::And(term.to_string())),
-            None => None,
-        })
-        .collect()
+
+/// Lowercases the title and drops everything but letters and digits so that
+/// "Amélie & Co." and "amélie and co" compare equal.
+pub fn normalize_title(title: &str) -> String {
+    title
+        .to_lowercase()
+        .replace("&", " and ")
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Removes accents and other diacritics, i.e. "Amélie" becomes "Amelie".
+pub fn strip_diacritics(s: &str) -> String {
+    s.nfd().filter(|c| !is_combining_mark(*c)).collect()
+}
+
+/// Lowercases any script and removes diacritics so that "Pokémon" and "POKEMON" compare equal.
+pub fn fold(s: &str) -> String {
+    strip_diacritics(&s.to_lowercase())
 }
 
+/// Maps the Netflix and OMDb types onto the latter, i.e. 'TV Show' onto 'series'.
+pub fn normalize_type(typ: &str) -> &'static str {
+    match &typ.to_lowercase()[..] {
+        "movie" => "movie",
+        "tv show" | "series" => "series",
+        _ => "",
+    }
+}
+
+/// Whether the filter has anything to search for, even if it doesn't parse yet.
 pub fn is_valid_query_filter(q: &str) -> bool {
-    !destructure_query_filter(q).is_empty()
+    !q.trim().is_empty()
 }
 
 const APP_INFO: AppInfo = AppInfo {
@@ -44,7 +108,7 @@ const APP_INFO: AppInfo = AppInfo {
     author: "thlorenz",
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DatabaseInfo {
     pub db_exists: bool,
     pub folder_exists: bool,
@@ -52,15 +116,69 @@ pub struct DatabaseInfo {
     pub db_path: PathBuf,
 }
 
-pub fn get_database_info() -> Result<DatabaseInfo, Box<dyn Error>> {
-    let data_root = get_app_root(AppDataType::UserData, &APP_INFO)?;
-    let folder_exists = data_root.exists();
-    let db_path = data_root.join("nf_rated.sqlite");
+/// Folder holding the config, response cache and, unless configured otherwise, the database.
+pub fn get_app_folder() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(get_app_root(AppDataType::UserData, &APP_INFO)?)
+}
+
+pub fn get_database_info(db_path: Option<PathBuf>) -> Result<DatabaseInfo, Box<dyn Error>> {
+    let db_path = match db_path {
+        Some(db_path) => db_path,
+        None => get_app_folder()?.join("nf_rated.sqlite"),
+    };
+    let folder = match db_path.parent() {
+        Some(parent) => parent.to_path_buf(),
+        None => PathBuf::from("."),
+    };
+    let folder_exists = folder.exists();
     let db_exists = db_path.exists();
     Ok(DatabaseInfo {
         folder_exists,
         db_exists,
-        folder: data_root,
+        folder,
         db_path,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("142 min"), (Some(142), None));
+        assert_eq!(parse_duration("1 Season"), (None, Some(1)));
+        assert_eq!(parse_duration("3 Seasons"), (None, Some(3)));
+        assert_eq!(parse_duration("N/A"), (None, None));
+    }
+
+    #[test]
+    fn dates_added() {
+        assert_eq!(
+            parse_date_added(" September 9, 2016"),
+            Some("2016-09-09".to_string())
+        );
+        assert_eq!(
+            parse_date_added("December 31, 2019"),
+            Some("2019-12-31".to_string())
+        );
+        assert_eq!(parse_date_added(""), None);
+    }
+
+    #[test]
+    fn folding() {
+        assert_eq!(fold("Pokémon"), "pokemon");
+        assert_eq!(fold("ÉLITE"), "elite");
+        assert_eq!(fold("Москва"), "москва");
+    }
+
+    #[test]
+    fn ages() {
+        let now = 1_600_000_000;
+        assert_eq!(format_age(now - 30, now), "just now");
+        assert_eq!(format_age(now - 60, now), "1 minute ago");
+        assert_eq!(format_age(now - 5 * 3600 - 1, now), "5 hours ago");
+        assert_eq!(format_age(now - 3 * 86400, now), "3 days ago");
+        assert_eq!(format_age(now + 10, now), "just now");
+    }
+}
@@ -1,9 +1,13 @@
+pub use self::config::*;
 pub use self::core::*;
+pub use self::query::*;
 pub use self::sync::*;
 pub use self::tui::*;
 pub mod data;
 pub mod render;
 
+mod config;
 mod core;
+mod query;
 mod sync;
 mod tui;
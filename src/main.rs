@@ -1,21 +1,227 @@
-use clap::{App, Arg, SubCommand};
-use nf_rated::{data::get_database_info, data::Db, sync_ratings, tui};
-use std::{error::Error, thread, time::Duration};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use nf_rated::{
+    audit, data::get_app_folder, data::get_database_info, data::Db, data::ItemType, data::Preset,
+    data::RangeColumn, data::RangeFilter, data::Sort, data::ANY_COLUMN, data::CAST_COLUMN,
+    data::COUNTRY_COLUMN, data::DEFAULT_PRIOR_VOTES, data::DIRECTOR_COLUMN, data::GENRE_COLUMN,
+    data::LANGUAGE_COLUMN, data::PLOT_COLUMN, data::TITLE_COLUMN, data::WRITER_COLUMN, import_imdb,
+    print_query_results, request_interval, resolve_optional_setting, resolve_setting, sync_daemon,
+    sync_ratings, sync_status, tui, BackgroundSync, Config, ResponseCache, SyncLock, SyncOptions,
+    SyncSelection, CONFIG_FILE, DB_PATH_VAR, ITEM_TYPE_VAR, OMDB_KEY_VAR, PRIOR_VOTES_VAR,
+    SORT_VAR, SYNC_LIMIT_VAR,
+};
+use std::{
+    error::Error, path::Path, process, str::FromStr, sync::atomic::Ordering, thread, time::Duration,
+};
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let db_info = get_database_info()?;
-    let db = Db::new(&db_info)?;
-    if !db_info.db_exists {
-        println!(
-            "The database did not exist yet and was created at: {:?}
-Make sure to sync ratings first by running 'nf-rated sync'.",
-            db_info.db_path
-        );
+const CACHE_TTL_DAYS: u64 = 30;
+const STALE_AFTER_DAYS: u64 = 30;
+const DAEMON_BATCH_SIZE: usize = 100;
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+const QUERY_LIMIT: usize = 50;
+
+const QUERY_COLUMNS: &[&str] = &[
+    ANY_COLUMN,
+    GENRE_COLUMN,
+    TITLE_COLUMN,
+    CAST_COLUMN,
+    COUNTRY_COLUMN,
+    LANGUAGE_COLUMN,
+    PLOT_COLUMN,
+    DIRECTOR_COLUMN,
+    WRITER_COLUMN,
+];
+const RANGE_ARGS: &[(&str, RangeColumn)] = &[
+    ("rating", RangeColumn::Rating),
+    ("votes", RangeColumn::Votes),
+    ("year", RangeColumn::Year),
+    ("runtime", RangeColumn::Runtime),
+    ("seasons", RangeColumn::Seasons),
+];
+
+fn number_arg<T: FromStr>(
+    matches: &ArgMatches,
+    name: &str,
+    default: T,
+) -> Result<T, Box<dyn Error>> {
+    match matches.value_of(name) {
+        Some(value) => value.parse::<T>().map_err(|_| {
+            format!(
+                "Invalid value '{}' for --{}, needs to be a number",
+                value, name
+            )
+            .into()
+        }),
+        None => Ok(default),
+    }
+}
+
+fn sync_options(
+    matches: &ArgMatches,
+    config: &Config,
+    cache_dir: &Path,
+) -> Result<SyncOptions, Box<dyn Error>> {
+    let defaults = SyncOptions::default();
+    let cache = if matches.is_present("no-cache") {
+        None
     } else {
-        println!("Found database at {:?}", db_info.db_path);
+        let ttl_days = number_arg(matches, "cache-ttl", CACHE_TTL_DAYS)?;
+        Some(ResponseCache::new(
+            cache_dir.to_path_buf(),
+            Duration::from_secs(ttl_days * SECS_PER_DAY),
+        ))
+    };
+    let requests_per_second = number_arg(matches, "rps", defaults.requests_per_second)?;
+    request_interval(requests_per_second).map_err(|err| format!("--rps: {}", err))?;
+    Ok(SyncOptions {
+        limit: resolve_setting(
+            "limit",
+            matches.value_of("limit"),
+            SYNC_LIMIT_VAR,
+            config.sync_limit,
+            defaults.limit,
+        )?,
+        concurrency: number_arg(matches, "concurrency", defaults.concurrency)?,
+        requests_per_second,
+        cache,
+        dry_run: matches.is_present("dry-run"),
+        ..defaults
+    })
+}
+
+fn sync_selection(matches: &ArgMatches) -> Result<SyncSelection, Box<dyn Error>> {
+    let ids = match matches.values_of("id") {
+        Some(ids) => ids
+            .map(|id| id.parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|_| "--id needs to be a show id, i.e. 80057281")?,
+        None => vec![],
+    };
+    let item_type = match matches.value_of("type") {
+        Some(item_type) => Some(item_type.parse::<ItemType>()?),
+        None => None,
+    };
+    Ok(SyncSelection {
+        ids,
+        title: matches.value_of("title").map(String::from),
+        genre: matches.value_of("genre").map(String::from),
+        item_type,
+    })
+}
+
+fn prior_votes(matches: &ArgMatches, config: &Config) -> Result<u32, Box<dyn Error>> {
+    resolve_setting(
+        "prior-votes",
+        matches.value_of("prior-votes"),
+        PRIOR_VOTES_VAR,
+        config.prior_votes,
+        DEFAULT_PRIOR_VOTES,
+    )
+}
+
+/// The preset given via `--preset` with the filters, type, sort and fuzziness given as flags
+/// on top, or only the latter if there is none. Without a preset the sort defaults to the
+/// configured one.
+fn query_preset(matches: &ArgMatches, config: &Config, db: &Db) -> Result<Preset, Box<dyn Error>> {
+    let mut preset = match matches.value_of("preset") {
+        Some(name) => match db.get_preset(name)? {
+            Some(preset) => preset,
+            None => {
+                let names: Vec<String> = db
+                    .get_presets()?
+                    .into_iter()
+                    .map(|preset| format!("'{}'", preset.name))
+                    .collect();
+                let saved = if names.is_empty() {
+                    "none are saved yet".to_string()
+                } else {
+                    format!("saved are {}", names.join(", "))
+                };
+                return Err(format!("Unknown preset '{}', {}", name, saved).into());
+            }
+        },
+        None => Preset {
+            sort: resolve_setting(
+                "sort",
+                matches.value_of("sort"),
+                SORT_VAR,
+                config.sort,
+                Sort::default(),
+            )?,
+            ..Default::default()
+        },
+    };
+    for column in QUERY_COLUMNS {
+        if let Some(query) = matches.value_of(column) {
+            preset.filters.insert(column.to_string(), query.to_string());
+        }
+    }
+    for (name, column) in RANGE_ARGS {
+        if let Some(range) = matches.value_of(name) {
+            preset
+                .ranges
+                .insert(column.name().to_string(), range.to_string());
+        }
+    }
+    if let Some(item_type) = matches.value_of("type") {
+        preset.item_type = item_type.parse()?;
+    }
+    if let Some(sort) = matches.value_of("sort") {
+        preset.sort = sort.parse()?;
+    }
+    if matches.is_present("fuzzy") {
+        preset.fuzzy = true;
     }
+    Ok(preset)
+}
+
+fn range_filters(preset: &Preset) -> Result<Vec<RangeFilter>, Box<dyn Error>> {
+    preset.range_filters().map_err(|err| {
+        let name = RANGE_ARGS
+            .iter()
+            .find(|(_, column)| err.column.as_deref() == Some(column.name()))
+            .map(|(name, _)| *name)
+            .unwrap_or_default();
+        format!("--{}: {}", name, err.message).into()
+    })
+}
 
+fn main() -> Result<(), Box<dyn Error>> {
     let matches = App::new("nf-rated")
+        .arg(
+            Arg::with_name("db")
+                .long("db")
+                .value_name("path")
+                .global(true)
+                .help("database to use, defaults to $NF_RATED_DB or 'db_path' in the config"),
+        )
+        .arg(
+            Arg::with_name("omdb-key")
+                .long("omdb-key")
+                .value_name("key")
+                .global(true)
+                .help("omdb API keys, comma separated, default to $OMDB_KEY or 'omdb_key' in the config"),
+        )
+        .arg(
+            Arg::with_name("prior-votes")
+                .long("prior-votes")
+                .value_name("votes")
+                .global(true)
+                .help("votes a title needs before its rating counts as much as the mean one in the weighted score"),
+        )
+        .arg(
+            Arg::with_name("type")
+                .short("t")
+                .long("type")
+                .value_name("movie|series|both")
+                .help("type of shows to list initially"),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .short("s")
+                .long("sort")
+                .value_name("key[:asc|desc]")
+                .help("initial sort, rating|weighted|votes|year|added|runtime|title, i.e. 'votes:asc'"),
+        )
         .subcommand(
             SubCommand::with_name("sync")
                 .about("syncs ratings from omdb")
@@ -25,28 +231,334 @@ Make sure to sync ratings first by running 'nf-rated sync'.",
                         .long("limit")
                         .value_name("limit")
                         .help("nf-rated -l <rate limit>"),
+                )
+                .arg(
+                    Arg::with_name("concurrency")
+                        .short("c")
+                        .long("concurrency")
+                        .value_name("concurrency")
+                        .help("number of requests in flight at the same time"),
+                )
+                .arg(
+                    Arg::with_name("rps")
+                        .long("rps")
+                        .value_name("rps")
+                        .help("maximum requests per second, 0 for no limit"),
+                )
+                .arg(
+                    Arg::with_name("no-cache")
+                        .long("no-cache")
+                        .help("always request from omdb instead of using cached responses"),
+                )
+                .arg(
+                    Arg::with_name("cache-ttl")
+                        .long("cache-ttl")
+                        .value_name("days")
+                        .help("days after which cached responses are requested again"),
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .help("lists the titles that would be synced without making requests"),
+                )
+                .arg(
+                    Arg::with_name("stale-after")
+                        .long("stale-after")
+                        .value_name("days")
+                        .help("syncs titles synced more than this many days ago once all others are synced"),
+                )
+                .arg(
+                    Arg::with_name("daemon")
+                        .long("daemon")
+                        .conflicts_with_all(&["dry-run", "report"])
+                        .help("keeps syncing in batches of --limit titles, waiting for requests to reset once used up"),
+                )
+                .arg(
+                    Arg::with_name("id")
+                        .long("id")
+                        .value_name("show_id")
+                        .multiple(true)
+                        .help(
+                            "syncs only the titles with these ids, even if they were synced before",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("title")
+                        .long("title")
+                        .value_name("query")
+                        .help("syncs only titles matching the query, i.e. 'ship !star'"),
+                )
+                .arg(
+                    Arg::with_name("genre")
+                        .long("genre")
+                        .value_name("query")
+                        .help("syncs only titles whose genre matches the query"),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .long("type")
+                        .value_name("movie|series")
+                        .help("syncs only titles of this type"),
+                )
+                .arg(
+                    Arg::with_name("report")
+                        .long("report")
+                        .value_name("format")
+                        .possible_values(&["json"])
+                        .help("prints a report of the sync results to stdout"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("query")
+                .about("prints the rated titles matching the filters, the best rated first")
+                .args(
+                    &QUERY_COLUMNS
+                        .iter()
+                        .map(|column| {
+                            Arg::with_name(column)
+                                .long(column)
+                                .value_name("query")
+                                .help("i.e. 'drama | crime !\"true story\"'")
+                        })
+                        .collect::<Vec<Arg>>(),
+                )
+                .arg(
+                    Arg::with_name("rating")
+                        .long("rating")
+                        .value_name("range")
+                        .allow_hyphen_values(true)
+                        .help("imdb rating, i.e. '7.5-' for at least 7.5"),
+                )
+                .arg(
+                    Arg::with_name("votes")
+                        .long("votes")
+                        .value_name("range")
+                        .allow_hyphen_values(true)
+                        .help("imdb votes, i.e. '10k-' for at least 10000"),
+                )
+                .arg(
+                    Arg::with_name("year")
+                        .long("year")
+                        .value_name("range")
+                        .allow_hyphen_values(true)
+                        .help("release year, i.e. '2010-2020'"),
+                )
+                .arg(
+                    Arg::with_name("runtime")
+                        .long("runtime")
+                        .value_name("range")
+                        .allow_hyphen_values(true)
+                        .help("runtime in minutes, of episodes for series, i.e. '-100' for at most 100"),
+                )
+                .arg(
+                    Arg::with_name("seasons")
+                        .long("seasons")
+                        .value_name("range")
+                        .allow_hyphen_values(true)
+                        .help("number of seasons of series, i.e. '2-'"),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .long("type")
+                        .value_name("movie|series|both")
+                        .help("type of shows to list"),
+                )
+                .arg(
+                    Arg::with_name("fuzzy")
+                        .long("fuzzy")
+                        .help("also match titles and cast with typos, the closest matches first"),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .long("sort")
+                        .value_name("key[:asc|desc]")
+                        .help("rating|weighted|votes|year|added|runtime|title, i.e. 'votes:asc'"),
+                )
+                .arg(
+                    Arg::with_name("preset")
+                        .long("preset")
+                        .short("p")
+                        .value_name("name")
+                        .help("filters saved in the TUI via Ctrl-W, flags given as well take precedence"),
+                )
+                .arg(
+                    Arg::with_name("limit")
+                        .short("l")
+                        .long("limit")
+                        .value_name("limit")
+                        .help("maximum number of titles to print"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("cache")
+                .about("manages cached omdb responses")
+                .subcommand(SubCommand::with_name("clear").about("removes all cached responses")),
+        )
+        .subcommand(
+            SubCommand::with_name("audit")
+                .about("lists synced titles that were likely matched with the wrong omdb title"),
+        )
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("shows how many titles are rated and how many omdb requests are left today"),
+        )
+        .subcommand(
+            SubCommand::with_name("import-imdb")
+                .about("imports ratings from the IMDb datasets (https://datasets.imdbws.com/)")
+                .arg(
+                    Arg::with_name("dir")
+                        .required(true)
+                        .value_name("dir")
+                        .help("folder containing title.basics.tsv.gz and title.ratings.tsv.gz"),
                 ),
         )
         .get_matches();
 
-    match matches.subcommand_matches("sync") {
-        Some(matches) => {
-            let limit = if matches.is_present("limit") {
-                matches
-                    .value_of("limit")
-                    .unwrap()
-                    .parse::<usize>()
-                    .expect("Limit needs to be a number, i.e. 1000")
-            } else {
-                1000
+    let app_folder = get_app_folder()?;
+    let config = Config::load(&app_folder.join(CONFIG_FILE))?;
+
+    let db_path = resolve_optional_setting(
+        "db",
+        matches.value_of("db"),
+        DB_PATH_VAR,
+        config.db_path.clone(),
+    )?;
+    let db_info = get_database_info(db_path)?;
+    let db = Db::new_with_progress(&db_info, |imported, total| {
+        eprint!("\rImporting Netflix titles {}/{}", imported, total);
+        if imported == total {
+            eprintln!();
+        }
+    })?;
+    // Keep stdout free for reports
+    if !db_info.db_exists {
+        eprintln!(
+            "The database did not exist yet and was created at: {:?}
+Make sure to sync ratings first by running 'nf-rated sync'.",
+            db_info.db_path
+        );
+    } else {
+        eprintln!("Found database at {:?}", db_info.db_path);
+    }
+
+    let cache_dir = app_folder.join("omdb_cache");
+    let api_keys = resolve_optional_setting(
+        "omdb-key",
+        matches.value_of("omdb-key"),
+        OMDB_KEY_VAR,
+        config.omdb_key.clone(),
+    )?
+    .map(|api_keys| api_keys.0)
+    .unwrap_or_default();
+    match matches.subcommand() {
+        ("sync", Some(matches)) => {
+            if api_keys.is_empty() {
+                eprintln!(
+                    "Please add an OMDB  API key as 'OMDB_KEY' to your environment or as 'omdb_key' to {:?}.
+You can obtain it from (http://www.omdbapi.com/).",
+                    app_folder.join(CONFIG_FILE)
+                );
+                process::exit(1)
+            }
+
+            let daemon = matches.is_present("daemon");
+            let defaults = sync_options(matches, &config, &cache_dir)?;
+            let stale_after_days = match matches.value_of("stale-after") {
+                Some(_) => Some(number_arg(matches, "stale-after", STALE_AFTER_DAYS)?),
+                None if daemon => Some(STALE_AFTER_DAYS),
+                None => None,
             };
-            sync_ratings(db, limit)?;
+            let opts = SyncOptions {
+                selection: sync_selection(matches)?,
+                stale_after: stale_after_days.map(|days| Duration::from_secs(days * SECS_PER_DAY)),
+                limit: if daemon && matches.value_of("limit").is_none() {
+                    DAEMON_BATCH_SIZE
+                } else {
+                    defaults.limit
+                },
+                ..defaults
+            };
+
+            let _lock = SyncLock::acquire(&db_info.db_path)?;
+            // Finish the titles in flight and record them instead of dropping them on the floor
+            let cancel = opts.cancel.clone();
+            ctrlc::set_handler(move || {
+                eprintln!("Stopping once the titles in flight are synced ...");
+                cancel.store(true, Ordering::SeqCst);
+            })?;
+
+            if daemon {
+                sync_daemon(&db, &api_keys, opts)?;
+                return Ok(());
+            }
+            let report = sync_ratings(db, &api_keys, opts)?;
+            if matches.value_of("report") == Some("json") {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+            process::exit(report.outcome.exit_code());
         }
-        None => {
+        ("cache", Some(matches)) => match matches.subcommand_name() {
+            Some("clear") => {
+                let cache = ResponseCache::new(cache_dir, Duration::from_secs(0));
+                println!("Removed {} cached responses", cache.clear()?);
+            }
+            _ => println!("{}", matches.usage()),
+        },
+        ("query", Some(matches)) => {
+            let preset = query_preset(matches, &config, &db)?;
+            print_query_results(
+                &db,
+                preset.column_filters(),
+                &range_filters(&preset)?,
+                &preset.item_type,
+                &preset.sort,
+                prior_votes(matches, &config)?,
+                number_arg(matches, "limit", QUERY_LIMIT)?,
+            )?;
+        }
+        ("audit", Some(_)) => {
+            audit(&db)?;
+        }
+        ("status", Some(_)) => {
+            sync_status(&db, &api_keys)?;
+        }
+        ("import-imdb", Some(matches)) => {
+            import_imdb(
+                db,
+                Path::new(matches.value_of("dir").unwrap()),
+                |imported, total| {
+                    eprint!("\rImporting IMDb ratings {}/{}", imported, total);
+                    if imported == total {
+                        eprintln!();
+                    }
+                },
+            )?;
+        }
+        _ => {
+            let background_sync = BackgroundSync {
+                opts: sync_options(&matches, &config, &cache_dir)?,
+                db_info: db_info.clone(),
+                api_keys,
+            };
+            let prior_votes = prior_votes(&matches, &config)?;
+            let item_type = resolve_setting(
+                "type",
+                matches.value_of("type"),
+                ITEM_TYPE_VAR,
+                config.item_type,
+                ItemType::Both,
+            )?;
+            let sort = resolve_setting(
+                "sort",
+                matches.value_of("sort"),
+                SORT_VAR,
+                config.sort,
+                Sort::default(),
+            )?;
             if !db_info.db_exists {
                 thread::sleep(Duration::from_millis(3000));
             }
-            tui(db)?
+            tui(db, item_type, sort, prior_votes, background_sync)?
         }
     }
 
@@ -0,0 +1,42 @@
+use crate::data::{
+    build_sorted_filtered_query, matched_fields, ColumnFilter, Db, ItemType, RangeFilter, Sort,
+};
+use std::error::Error;
+
+/// Prints the titles matching the filters in the given order, with the fields the terms of the
+/// any filter matched.
+pub fn print_query_results(
+    db: &Db,
+    filters: Vec<ColumnFilter>,
+    ranges: &[RangeFilter],
+    item_type: &ItemType,
+    sort: &Sort,
+    prior_votes: u32,
+    limit: usize,
+) -> Result<(), Box<dyn Error>> {
+    let any_terms: Vec<String> = filters
+        .iter()
+        .flat_map(|filter| filter.any_terms())
+        .collect();
+    let query = build_sorted_filtered_query(filters, ranges, item_type, sort, prior_votes)?;
+    let rows = db.get_query_result(&query)?;
+    for row in rows.iter().take(limit) {
+        let matched = if any_terms.is_empty() {
+            "".to_string()
+        } else {
+            format!("  in {}", matched_fields(row, &any_terms).join(", "))
+        };
+        println!(
+            "{:>4.1} {:>4.1} {:>9} {} {:<6} {}{}",
+            row.imdb_rating.unwrap_or_default() as f32 / 10.0,
+            row.weighted_rating.unwrap_or_default() / 10.0,
+            row.imdb_votes.unwrap_or_default(),
+            row.year,
+            row.typ,
+            row.title,
+            matched,
+        );
+    }
+    eprintln!("Showing {} of {} titles", rows.len().min(limit), rows.len());
+    Ok(())
+}
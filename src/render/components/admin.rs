@@ -5,27 +5,38 @@ use tui::{
     layout::Layout,
     layout::Rect,
     style::{Color, Style},
-    text::Span,
+    text::{Span, Spans},
     widgets::Block,
     widgets::BorderType,
     widgets::Borders,
+    widgets::Gauge,
     widgets::Paragraph,
     widgets::Row,
     widgets::Table,
     Frame,
 };
 
-use crate::{data::ItemType, render::App, render::QueryField};
-
-pub fn query_offset(query_field: &QueryField) -> u16 {
-    3 * match query_field {
-        QueryField::Genre => 0,
-        QueryField::Title => 1,
-        QueryField::Cast => 2,
-        QueryField::Country => 3,
-        QueryField::Language => 4,
-        QueryField::Plot => 5,
-    } + 2
+use crate::{
+    data::ItemType, data::QuerySyntaxError, data::RangeColumn, data::Sort, data::ANY_COLUMN,
+    data::CAST_COLUMN, data::COUNTRY_COLUMN, data::DIRECTOR_COLUMN, data::GENRE_COLUMN,
+    data::LANGUAGE_COLUMN, data::PLOT_COLUMN, data::TITLE_COLUMN, data::WRITER_COLUMN, render::App,
+    render::QueryField, render::SyncProgress,
+};
+
+// The type line and nine lines of inputs with a border each
+const INPUTS_HEIGHT: u16 = 1 + 9 * 3;
+const SHORTCUTS_HEIGHT: u16 = 16;
+const LEGEND_HEIGHT: u16 = 11;
+// Borders, header and one row, tables with less room are left out
+const MIN_TABLE_HEIGHT: u16 = 4;
+
+/// Height of a table given the rows left below the inputs, so that the help tables shrink and
+/// then disappear on small terminals instead of squeezing the inputs.
+fn table_height(available: u16, height: u16) -> u16 {
+    match available.min(height) {
+        height if height < MIN_TABLE_HEIGHT => 0,
+        height => height,
+    }
 }
 
 pub fn render_admin<B>(f: &mut Frame<B>, app: &App, container: Rect)
@@ -38,13 +49,6 @@ where
         .split(container);
     let config_container = chunks[0];
 
-    f.set_cursor(
-        // Put cursor past the end of the input text
-        config_container.x + app.get_query().len() as u16 + 1,
-        // Move one line down, from the border to the input line
-        config_container.y + query_offset(&app.query_field),
-    );
-
     render_config(f, &app, config_container);
 }
 
@@ -52,6 +56,9 @@ pub fn render_config<B>(f: &mut Frame<B>, app: &App, container: Rect)
 where
     B: Backend,
 {
+    let available = container.height.saturating_sub(INPUTS_HEIGHT);
+    let shortcuts_height = table_height(available, SHORTCUTS_HEIGHT);
+    let legend_height = table_height(available - shortcuts_height, LEGEND_HEIGHT);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
@@ -63,30 +70,101 @@ where
                 Constraint::Length(3),
                 Constraint::Length(3),
                 Constraint::Length(3),
-                Constraint::Length(10),
-                Constraint::Length(10),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(shortcuts_height),
+                Constraint::Length(legend_height),
+                Constraint::Min(0),
             ]
             .as_ref(),
         )
         .split(container);
 
     let item_type_container = chunks[0];
-    let genre_query_container = chunks[1];
-    let title_query_container = chunks[2];
-    let cast_query_container = chunks[3];
-    let country_query_container = chunks[4];
-    let language_query_container = chunks[5];
-    let plot_query_container = chunks[6];
-    let keyboard_shortcuts_container = chunks[7];
-    let queries_container = chunks[8];
-
-    let item_type_ui = render_item_type(&app.item_type);
-    f.render_widget(item_type_ui, item_type_container);
+    let any_query_container = chunks[1];
+    let genre_query_container = chunks[2];
+    let title_query_container = chunks[3];
+    let cast_query_container = chunks[4];
+    let country_query_container = chunks[5];
+    let language_query_container = chunks[6];
+    let plot_query_container = chunks[7];
+    let people_containers = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50); 2].as_ref())
+        .split(chunks[8]);
+    let director_query_container = people_containers[0];
+    let writer_query_container = people_containers[1];
+    let range_containers = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(20); 5].as_ref())
+        .split(chunks[9]);
+    let rating_range_container = range_containers[0];
+    let votes_range_container = range_containers[1];
+    let year_range_container = range_containers[2];
+    let runtime_range_container = range_containers[3];
+    let seasons_range_container = range_containers[4];
+    let keyboard_shortcuts_container = chunks[10];
+    let queries_container = chunks[11];
+
+    let selected_container = match app.query_field {
+        QueryField::Any => any_query_container,
+        QueryField::Genre => genre_query_container,
+        QueryField::Title => title_query_container,
+        QueryField::Cast => cast_query_container,
+        QueryField::Country => country_query_container,
+        QueryField::Language => language_query_container,
+        QueryField::Plot => plot_query_container,
+        QueryField::Director => director_query_container,
+        QueryField::Writer => writer_query_container,
+        QueryField::Rating => rating_range_container,
+        QueryField::Votes => votes_range_container,
+        QueryField::Year => year_range_container,
+        QueryField::Runtime => runtime_range_container,
+        QueryField::Seasons => seasons_range_container,
+    };
+    f.set_cursor(
+        // Put cursor past the end of the input text
+        selected_container.x + app.get_query().chars().count() as u16 + 1,
+        // Move one line down, from the border to the input line
+        selected_container.y + 1,
+    );
+
+    let item_type_ui = render_item_type(&app.item_type, &app.sort, app.fuzzy, app.suspicious_only);
+    let split_item_type_container = || {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+            .split(item_type_container)
+    };
+    // Changes made by another process matter more than the outcome of a past sync
+    match (app.db_changed, &app.sync_progress) {
+        (true, _) => {
+            let chunks = split_item_type_container();
+            f.render_widget(item_type_ui, chunks[0]);
+            f.render_widget(render_db_changed(), chunks[1]);
+        }
+        (false, Some(sync_progress)) => {
+            let chunks = split_item_type_container();
+            f.render_widget(item_type_ui, chunks[0]);
+            f.render_widget(render_sync_progress(sync_progress), chunks[1]);
+        }
+        (false, None) => f.render_widget(item_type_ui, item_type_container),
+    }
+
+    let any_query_ui = render_query(
+        "Any Field",
+        &app.any_query,
+        app.query_field == QueryField::Any,
+        app.query_error_for(ANY_COLUMN),
+    );
+    f.render_widget(any_query_ui, any_query_container);
 
     let genre_query_ui = render_query(
         "Genre",
         &app.genre_query,
         &app.query_field == &QueryField::Genre,
+        app.query_error_for(GENRE_COLUMN),
     );
     f.render_widget(genre_query_ui, genre_query_container);
 
@@ -94,6 +172,7 @@ where
         "Title",
         &app.title_query,
         &app.query_field == &QueryField::Title,
+        app.query_error_for(TITLE_COLUMN),
     );
     f.render_widget(title_query_ui, title_query_container);
 
@@ -101,6 +180,7 @@ where
         "Cast",
         &app.cast_query,
         &app.query_field == &QueryField::Cast,
+        app.query_error_for(CAST_COLUMN),
     );
     f.render_widget(cast_query_ui, cast_query_container);
 
@@ -108,6 +188,7 @@ where
         "Country",
         &app.country_query,
         &app.query_field == &QueryField::Country,
+        app.query_error_for(COUNTRY_COLUMN),
     );
     f.render_widget(country_query_ui, country_query_container);
 
@@ -115,6 +196,7 @@ where
         "Language",
         &app.language_query,
         &app.query_field == &QueryField::Language,
+        app.query_error_for(LANGUAGE_COLUMN),
     );
     f.render_widget(language_query_ui, language_query_container);
 
@@ -122,11 +204,79 @@ where
         "Plot",
         &app.plot_query,
         &app.query_field == &QueryField::Plot,
+        app.query_error_for(PLOT_COLUMN),
     );
     f.render_widget(plot_query_ui, plot_query_container);
 
-    render_keyboard_shortcuts(f, keyboard_shortcuts_container);
-    render_queries_legend(f, queries_container);
+    let director_query_ui = render_query(
+        "Director",
+        &app.director_query,
+        app.query_field == QueryField::Director,
+        app.query_error_for(DIRECTOR_COLUMN),
+    );
+    f.render_widget(director_query_ui, director_query_container);
+
+    let writer_query_ui = render_query(
+        "Writer",
+        &app.writer_query,
+        app.query_field == QueryField::Writer,
+        app.query_error_for(WRITER_COLUMN),
+    );
+    f.render_widget(writer_query_ui, writer_query_container);
+
+    let ranges = [
+        (
+            "Rating",
+            &app.rating_range,
+            QueryField::Rating,
+            RangeColumn::Rating,
+            rating_range_container,
+        ),
+        (
+            "Votes",
+            &app.votes_range,
+            QueryField::Votes,
+            RangeColumn::Votes,
+            votes_range_container,
+        ),
+        (
+            "Year",
+            &app.year_range,
+            QueryField::Year,
+            RangeColumn::Year,
+            year_range_container,
+        ),
+        (
+            "Minutes",
+            &app.runtime_range,
+            QueryField::Runtime,
+            RangeColumn::Runtime,
+            runtime_range_container,
+        ),
+        (
+            "Seasons",
+            &app.seasons_range,
+            QueryField::Seasons,
+            RangeColumn::Seasons,
+            seasons_range_container,
+        ),
+    ];
+    for (label, range, query_field, column, container) in ranges.iter() {
+        let range_ui = render_query(
+            label,
+            range,
+            &app.query_field == query_field,
+            app.query_error_for(column.name()),
+        );
+        f.render_widget(range_ui, *container);
+    }
+
+    if shortcuts_height > 0 {
+        render_keyboard_shortcuts(f, keyboard_shortcuts_container);
+    }
+    if legend_height > 0 {
+        render_queries_legend(f, queries_container);
+    }
 }
 
 fn render_keyboard_shortcuts<B>(f: &mut Frame<B>, container: Rect)
@@ -143,6 +293,13 @@ where
         vec!["Ctrl-D/Ctrl-U", "Select Next/Previous Page"],
         vec!["Ctrl-E", "Erase all Filters"],
         vec!["Ctrl-O", "Change Type of Show"],
+        vec!["Ctrl-S/Ctrl-T", "Change Sort/Reverse Sort Order"],
+        vec!["Ctrl-Y", "Sync Ratings in the Background"],
+        vec!["Ctrl-R", "Sync Selected Show Again"],
+        vec!["Ctrl-F", "Toggle Fuzzy Title and Cast Search"],
+        vec!["Ctrl-G/Ctrl-W", "Load/Save Filters as Preset"],
+        vec!["Ctrl-A", "Toggle Suspicious Matches"],
+        vec!["Ctrl-L", "Reload Changed Ratings"],
     ];
 
     let rows = items
@@ -167,9 +324,23 @@ where
     let items = vec![
         vec![
             "drama adventure",
-            "Match all that has 'drama' or 'adventure'",
+            "Match all that has 'drama' and 'adventure'",
         ],
+        vec![
+            "drama | adventure",
+            "Match all that has 'drama' or 'adventure', also OR",
+        ],
+        vec!["\"science fiction\"", "Match the whole phrase"],
         vec!["!comedy", "Match all that does not have 'comedy'"],
+        vec!["!(comedy | family)", "Group terms with parentheses"],
+        vec![
+            "cast:hanks",
+            "Match another field, i.e. director, writer or any",
+        ],
+        vec![
+            "7.5- | -100 | 2010-2020",
+            "Ranges are at least/at most/between",
+        ],
     ];
 
     let rows = items
@@ -183,7 +354,12 @@ where
     f.render_widget(t, container);
 }
 
-fn render_item_type(item_type: &ItemType) -> Paragraph {
+fn render_item_type<'a>(
+    item_type: &ItemType,
+    sort: &Sort,
+    fuzzy: bool,
+    suspicious_only: bool,
+) -> Paragraph<'a> {
     let value = match item_type {
         ItemType::Movie => "Movies",
         ItemType::Series => "Series",
@@ -192,15 +368,63 @@ fn render_item_type(item_type: &ItemType) -> Paragraph {
     let value_style = Style::default().fg(Color::LightBlue);
     let value_span = Span::styled(value, value_style);
 
-    Paragraph::new(value_span)
+    // Suspicious matches are always listed the least likely first
+    if suspicious_only {
+        let audit_style = Style::default().fg(Color::LightRed);
+        let audit_span = Span::styled(" (suspicious matches)", audit_style);
+        Paragraph::new(Spans::from(vec![value_span, audit_span]))
+    } else {
+        let sort_style = Style::default().fg(Color::Gray);
+        let sort = if fuzzy {
+            format!(" by closest match, {}", sort)
+        } else {
+            format!(" by {}", sort)
+        };
+        let sort_span = Span::styled(sort, sort_style);
+        Paragraph::new(Spans::from(vec![value_span, sort_span]))
+    }
 }
 
-fn render_query<'a>(label: &'a str, query: &'a str, selected: bool) -> Paragraph<'a> {
-    let label_style = Style::default().fg(Color::Gray);
-    let border_style = if selected {
-        Style::default().fg(Color::Yellow)
+fn render_db_changed<'a>() -> Paragraph<'a> {
+    Paragraph::new(Span::styled(
+        "Ratings changed, press Ctrl-L to reload",
+        Style::default().fg(Color::Yellow),
+    ))
+}
+
+fn render_sync_progress(sync_progress: &SyncProgress) -> Gauge<'_> {
+    let color = if sync_progress.finished {
+        Color::Green
     } else {
-        Style::default().fg(Color::White)
+        Color::LightBlue
+    };
+    Gauge::default()
+        .gauge_style(Style::default().fg(color).bg(Color::Black))
+        .ratio(sync_progress.ratio())
+        .label(sync_progress.status.as_str())
+}
+
+fn render_query<'a>(
+    label: &'a str,
+    query: &'a str,
+    selected: bool,
+    error: Option<&QuerySyntaxError>,
+) -> Paragraph<'a> {
+    let label_style = Style::default().fg(Color::Gray);
+    let border_style = match (error, selected) {
+        (Some(_), _) => Style::default().fg(Color::LightRed),
+        (None, true) => Style::default().fg(Color::Yellow),
+        (None, false) => Style::default().fg(Color::White),
+    };
+    let title = match error {
+        Some(err) => Spans::from(vec![
+            Span::raw(label),
+            Span::styled(
+                format!(" - {} at {}", err.message, err.position + 1),
+                Style::default().fg(Color::LightRed),
+            ),
+        ]),
+        None => Spans::from(label),
     };
 
     let query_style = Style::default().fg(Color::White);
@@ -212,6 +436,6 @@ fn render_query<'a>(label: &'a str, query: &'a str, selected: bool) -> Paragraph
             .border_style(border_style)
             .border_type(BorderType::Rounded)
             .style(label_style)
-            .title(label),
+            .title(title),
     )
 }
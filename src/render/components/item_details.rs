@@ -3,9 +3,9 @@ use tui::{
     widgets::Borders, widgets::List, widgets::ListItem,
 };
 
-use crate::RatedRow;
+use crate::{data::format_age, data::unix_now, RatedRow};
 
-use super::util::get_rating_style;
+use super::util::{format_runtime, get_rating_style};
 
 pub fn maybe_render_item_details(row: Option<&RatedRow>) -> List {
     // TODO: forget about the block for now but need to fix ASAP
@@ -31,6 +31,19 @@ fn render_please_select_row() -> Vec<ListItem<'static>> {
 
 const N_A: &str = "N/A";
 
+/// "Directed by ... | Written by ...", leaving out what neither Netflix nor OMDb know.
+fn format_people(row: &RatedRow) -> String {
+    let known = |people: &str| !people.trim().is_empty() && people != N_A;
+    let mut people = vec![];
+    if known(&row.director) {
+        people.push(format!("Directed by {}", row.director));
+    }
+    if let Some(writer) = row.writer.as_deref().filter(|writer| known(writer)) {
+        people.push(format!("Written by {}", writer));
+    }
+    people.join(" | ")
+}
+
 fn render_row_summary(row: &RatedRow) -> Vec<ListItem> {
     let bar = Span::raw(" | ");
     let spc = Span::raw(" ");
@@ -46,13 +59,21 @@ fn render_row_summary(row: &RatedRow) -> Vec<ListItem> {
     let rating = row.imdb_rating.unwrap();
     let rating_style = get_rating_style(rating);
     let rating_span = Span::styled(format!(" {:2.1}", rating as f32 / 10.0), rating_style);
+    let weighted_style = Style::default().fg(Color::DarkGray);
+    let weighted_span = match row.weighted_rating {
+        Some(weighted) => Span::styled(
+            format!(" ({:.1} weighted)", weighted / 10.0),
+            weighted_style,
+        ),
+        None => Span::raw(""),
+    };
 
     let title_style = Style::default().fg(Color::White);
     let title_span = Span::styled(&row.title, title_style);
     let year_style = Style::default().fg(Color::DarkGray);
     let year_span = Span::styled(format!("({})", row.year), year_style);
     let duration_style = Style::default().fg(Color::DarkGray);
-    let duration_span = Span::styled(&row.duration, duration_style);
+    let duration_span = Span::styled(format_runtime(row), duration_style);
 
     let genre_style = Style::default().fg(Color::LightBlue);
     let genre_span = match &row.genre {
@@ -63,6 +84,9 @@ fn render_row_summary(row: &RatedRow) -> Vec<ListItem> {
     let cast_style = Style::default().fg(Color::Blue);
     let cast_span = Span::styled(&row.cast, cast_style);
 
+    let people_style = Style::default().fg(Color::Gray);
+    let people_span = Span::styled(format_people(row), people_style);
+
     let plot_style = Style::default().fg(Color::White);
     let plot_span = Span::styled(&row.plot, plot_style);
 
@@ -83,6 +107,34 @@ fn render_row_summary(row: &RatedRow) -> Vec<ListItem> {
     let country_style = Style::default().fg(Color::LightGreen);
     let country_span = Span::styled(&row.country, country_style);
 
+    let synced_style = Style::default().fg(Color::DarkGray);
+    let synced_span = match row.last_sync {
+        Some(last_sync) => Span::styled(
+            format!("synced {}", format_age(last_sync, unix_now())),
+            synced_style,
+        ),
+        None => Span::styled("not synced yet", synced_style),
+    };
+
+    let match_span = match (&row.match_issues, &row.omdb_title) {
+        (Some(issues), Some(omdb_title)) => {
+            let match_style = Style::default().fg(Color::LightRed);
+            Span::styled(
+                format!(
+                    "Matched '{}' ({}) with {}% confidence, check {}",
+                    omdb_title,
+                    row.omdb_year
+                        .map(|year| year.to_string())
+                        .unwrap_or_else(|| N_A.to_string()),
+                    row.match_confidence.unwrap_or_default(),
+                    issues.replace(",", ", ")
+                ),
+                match_style,
+            )
+        }
+        _ => Span::raw(""),
+    };
+
     // TODO: how/where can we add the plot as paragraph instead of a list item
     // let plot_para = Paragraph::new(plot_span).wrap(Wrap { trim: true });
     // see tui-rs/examples/demo/ui.rs:271
@@ -90,20 +142,25 @@ fn render_row_summary(row: &RatedRow) -> Vec<ListItem> {
     vec![
         ListItem::new(Spans(vec![
             rating_span,
+            weighted_span,
             bar.clone(),
             duration_span,
             bar.clone(),
             title_span,
             spc.clone(),
             year_span,
+            bar.clone(),
+            synced_span,
         ])),
         ListItem::new(Spans(vec![])),
         ListItem::new(Spans(vec![genre_span, bar, country_span])),
         ListItem::new(cast_span),
+        ListItem::new(people_span),
         ListItem::new(Spans(vec![])),
         ListItem::new(plot_span),
         ListItem::new(Spans(vec![])),
         ListItem::new(imdblink_span),
         ListItem::new(netflixlink_span),
+        ListItem::new(match_span),
     ]
 }
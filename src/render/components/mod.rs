@@ -1,11 +1,13 @@
 pub use self::admin::*;
 pub use self::item_details::*;
 pub use self::log::*;
+pub use self::preset_popup::*;
 pub use self::row_summary::*;
 pub use self::rows_summary::*;
 mod admin;
 mod item_details;
 mod log;
+mod preset_popup;
 mod row_summary;
 mod rows_summary;
 mod util;
@@ -0,0 +1,83 @@
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::Block,
+    widgets::Borders,
+    widgets::Clear,
+    widgets::Paragraph,
+    widgets::{List, ListItem},
+    Frame,
+};
+
+use crate::{data::Preset, render::PresetPopup};
+
+const POPUP_WIDTH: u16 = 60;
+const POPUP_HEIGHT: u16 = 12;
+
+/// Centers a rect of the given size in the container, shrinking it if the container is smaller.
+fn centered_rect(width: u16, height: u16, container: Rect) -> Rect {
+    let width = width.min(container.width);
+    let height = height.min(container.height);
+    Rect::new(
+        container.x + (container.width - width) / 2,
+        container.y + (container.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+pub fn render_preset_popup<B>(f: &mut Frame<B>, popup: &mut PresetPopup, container: Rect)
+where
+    B: Backend,
+{
+    match popup {
+        PresetPopup::Load(presets) => {
+            let area = centered_rect(POPUP_WIDTH, POPUP_HEIGHT, container);
+            let title = "Load Preset (<Enter> load, <Del> delete, <Esc> close)";
+            let items: Vec<ListItem> = if presets.items.is_empty() {
+                vec![ListItem::new(Span::styled(
+                    "No presets yet, save the current filters with Ctrl-W",
+                    Style::default().fg(Color::Gray),
+                ))]
+            } else {
+                presets.items.iter().map(render_preset).collect()
+            };
+            let list = List::new(items)
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .highlight_style(
+                    Style::default()
+                        .bg(Color::DarkGray)
+                        .add_modifier(Modifier::BOLD),
+                );
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut presets.state);
+        }
+        PresetPopup::Save(name) => {
+            let area = centered_rect(POPUP_WIDTH, 3, container);
+            let title = "Save Filters as (<Enter> save, <Esc> cancel)";
+            let input = Paragraph::new(Span::styled(
+                name.as_str(),
+                Style::default().fg(Color::Yellow),
+            ))
+            .block(Block::default().borders(Borders::ALL).title(title));
+            f.render_widget(Clear, area);
+            f.render_widget(input, area);
+        }
+    }
+}
+
+fn render_preset(preset: &Preset) -> ListItem<'_> {
+    let filters = preset
+        .filters
+        .values()
+        .chain(preset.ranges.values())
+        .map(|query| query.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ");
+    ListItem::new(Spans::from(vec![
+        Span::raw(preset.name.as_str()),
+        Span::styled(format!("  {}", filters), Style::default().fg(Color::Gray)),
+    ]))
+}
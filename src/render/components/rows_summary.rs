@@ -4,8 +4,22 @@ use tui::{
     widgets::ListItem,
 };
 
-pub fn render_rows_summary(rows: &Vec<RatedRow>) -> List {
-    let rendered_rows: Vec<ListItem> = rows.iter().map(|row| render_row_summary(row)).collect();
+/// Lists the rows, each with the fields the any field matched if there are any.
+pub fn render_rows_summary<'a>(
+    rows: &'a Vec<RatedRow>,
+    matched_fields: &[Vec<&'static str>],
+) -> List<'a> {
+    let rendered_rows: Vec<ListItem> = rows
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| {
+            let matched = matched_fields
+                .get(idx)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            render_row_summary(row, matched)
+        })
+        .collect();
 
     List::new(rendered_rows)
         .block(Block::default().borders(Borders::ALL))
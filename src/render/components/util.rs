@@ -1,5 +1,30 @@
 use tui::style::{Color, Style};
 
+use crate::RatedRow;
+
+fn format_minutes(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, mins) => format!("{}m", mins),
+        (hours, mins) => format!("{}h {}m", hours, mins),
+    }
+}
+
+/// The runtime formatted the same way for all titles, i.e. "1h 42m" or "3 Seasons, 45m episodes".
+pub fn format_runtime(row: &RatedRow) -> String {
+    let seasons = row.season_count.map(|count| match count {
+        1 => "1 Season".to_string(),
+        count => format!("{} Seasons", count),
+    });
+    match (seasons, row.runtime_minutes) {
+        (Some(seasons), Some(minutes)) => {
+            format!("{}, {} episodes", seasons, format_minutes(minutes))
+        }
+        (Some(seasons), None) => seasons,
+        (None, Some(minutes)) => format_minutes(minutes),
+        (None, None) => row.duration.clone(),
+    }
+}
+
 pub fn get_rating_style(rating: u32) -> Style {
     match rating {
         n if n >= 90 => Style::default().fg(Color::LightGreen),
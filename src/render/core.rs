@@ -1,4 +1,12 @@
-use crate::{data::is_valid_query_filter, data::ItemType, RatedRow};
+use std::sync::mpsc::Receiver;
+
+use crate::{
+    data::is_valid_query_filter, data::ItemType, data::Preset, data::QuerySyntaxError,
+    data::RangeColumn, data::RangeFilter, data::Sort, data::ANY_COLUMN, data::CAST_COLUMN,
+    data::COUNTRY_COLUMN, data::DEFAULT_PRIOR_VOTES, data::DIRECTOR_COLUMN, data::GENRE_COLUMN,
+    data::LANGUAGE_COLUMN, data::PLOT_COLUMN, data::RANGE_COLUMNS, data::TITLE_COLUMN,
+    data::WRITER_COLUMN, RatedRow, SyncEvent,
+};
 
 use super::StatefulList;
 
@@ -18,41 +26,120 @@ pub enum Log {
 
 #[derive(Clone, PartialEq)]
 pub enum QueryField {
+    Any,
     Genre,
     Title,
     Cast,
     Country,
     Language,
     Plot,
+    Director,
+    Writer,
+    Rating,
+    Votes,
+    Year,
+    Runtime,
+    Seasons,
 }
 
 impl From<QueryField> for String {
     fn from(field: QueryField) -> Self {
         match field {
+            QueryField::Any => "Any".to_string(),
             QueryField::Genre => "Genre".to_string(),
             QueryField::Title => "Title".to_string(),
             QueryField::Cast => "Cast".to_string(),
             QueryField::Country => "Country".to_string(),
             QueryField::Language => "Language".to_string(),
             QueryField::Plot => "Plot".to_string(),
+            QueryField::Director => "Director".to_string(),
+            QueryField::Writer => "Writer".to_string(),
+            QueryField::Rating => "Rating".to_string(),
+            QueryField::Votes => "Votes".to_string(),
+            QueryField::Year => "Year".to_string(),
+            QueryField::Runtime => "Runtime".to_string(),
+            QueryField::Seasons => "Seasons".to_string(),
         }
     }
 }
 
+/// Progress of a sync running in the background while the TUI is up.
+#[derive(Default)]
+pub struct SyncProgress {
+    pub total: usize,
+    pub done: usize,
+    pub synced: usize,
+    pub status: String,
+    pub finished: bool,
+}
+
+impl SyncProgress {
+    pub fn new(status: &str) -> Self {
+        Self {
+            status: status.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn ratio(&self) -> f64 {
+        if self.finished {
+            1.0
+        } else if self.total == 0 {
+            0.0
+        } else {
+            (self.done as f64 / self.total as f64).min(1.0)
+        }
+    }
+}
+
+/// Popup listing the saved presets or asking for the name to save the current filters under.
+pub enum PresetPopup {
+    Load(StatefulList<Preset>),
+    Save(String),
+}
+
 pub struct App {
     pub items: StatefulList<RatedRow>,
 
     pub query_field: QueryField,
+    pub any_query: String,
     pub genre_query: String,
     pub title_query: String,
     pub cast_query: String,
     pub country_query: String,
     pub language_query: String,
     pub plot_query: String,
+    pub director_query: String,
+    pub writer_query: String,
+    pub rating_range: String,
+    pub votes_range: String,
+    pub year_range: String,
+    pub runtime_range: String,
+    pub seasons_range: String,
+    /// The fields of each item the any field's terms matched, empty without any terms.
+    pub matched_fields: Vec<Vec<&'static str>>,
+    /// Why the filters don't parse, the last results stay listed until they do.
+    pub query_error: Option<QuerySyntaxError>,
 
     pub item_type: ItemType,
+    pub sort: Sort,
+    /// How many votes the weighted score assumes every title has at the mean rating.
+    pub prior_votes: u32,
+    /// Titles and cast also match terms with typos, the closest matches first.
+    pub fuzzy: bool,
+    /// Only list titles whose OMDb match looks wrong.
+    pub suspicious_only: bool,
     pub logs: Vec<Log>,
     pub input_mode: InputMode,
+    pub preset_popup: Option<PresetPopup>,
+
+    pub sync_events: Option<Receiver<SyncEvent>>,
+    pub sync_progress: Option<SyncProgress>,
+
+    /// The database's data version when the items were last queried.
+    pub data_version: i64,
+    /// Another process changed the database since, i.e. a sync that runs in a terminal.
+    pub db_changed: bool,
 }
 
 impl App {
@@ -60,20 +147,48 @@ impl App {
         Self {
             items: StatefulList::with_items(rows),
 
-            query_field: QueryField::Genre,
+            query_field: QueryField::Any,
+            any_query: "".to_string(),
             genre_query: "".to_string(),
             title_query: "".to_string(),
             cast_query: "".to_string(),
             country_query: "".to_string(),
             language_query: "".to_string(),
             plot_query: "".to_string(),
+            director_query: "".to_string(),
+            writer_query: "".to_string(),
+            rating_range: "".to_string(),
+            votes_range: "".to_string(),
+            year_range: "".to_string(),
+            runtime_range: "".to_string(),
+            seasons_range: "".to_string(),
+            matched_fields: vec![],
+            query_error: None,
 
             item_type: ItemType::Both,
+            sort: Sort::default(),
+            prior_votes: DEFAULT_PRIOR_VOTES,
+            fuzzy: false,
+            suspicious_only: false,
             logs: vec![],
             input_mode: InputMode::Querying,
+            preset_popup: None,
+
+            sync_events: None,
+            sync_progress: None,
+
+            data_version: 0,
+            db_changed: false,
         }
     }
 
+    pub fn selected_item(&self) -> Option<&RatedRow> {
+        self.items
+            .state
+            .selected()
+            .and_then(|idx| self.items.items.get(idx))
+    }
+
     pub fn next_item_type(&mut self) {
         let next_type = match self.item_type {
             ItemType::Movie => ItemType::Series,
@@ -83,83 +198,259 @@ impl App {
         self.item_type = next_type;
     }
 
+    /// Sorts by the next key in its default direction.
+    pub fn next_sort(&mut self) {
+        self.sort = Sort::new(self.sort.key.next());
+    }
+
+    pub fn toggle_sort_direction(&mut self) {
+        self.sort.direction = self.sort.direction.toggle();
+    }
+
     pub fn next_query_field(&mut self) {
         let next_query_field = match self.query_field {
+            QueryField::Any => QueryField::Genre,
             QueryField::Genre => QueryField::Title,
             QueryField::Title => QueryField::Cast,
             QueryField::Cast => QueryField::Country,
             QueryField::Country => QueryField::Language,
             QueryField::Language => QueryField::Plot,
-            QueryField::Plot => QueryField::Genre,
+            QueryField::Plot => QueryField::Director,
+            QueryField::Director => QueryField::Writer,
+            QueryField::Writer => QueryField::Rating,
+            QueryField::Rating => QueryField::Votes,
+            QueryField::Votes => QueryField::Year,
+            QueryField::Year => QueryField::Runtime,
+            QueryField::Runtime => QueryField::Seasons,
+            QueryField::Seasons => QueryField::Any,
         };
         self.query_field = next_query_field
     }
 
     pub fn prev_query_field(&mut self) {
         let prev_query_field = match self.query_field {
+            QueryField::Seasons => QueryField::Runtime,
+            QueryField::Runtime => QueryField::Year,
+            QueryField::Year => QueryField::Votes,
+            QueryField::Votes => QueryField::Rating,
+            QueryField::Rating => QueryField::Writer,
+            QueryField::Writer => QueryField::Director,
+            QueryField::Director => QueryField::Plot,
             QueryField::Plot => QueryField::Language,
             QueryField::Country => QueryField::Cast,
             QueryField::Language => QueryField::Country,
             QueryField::Cast => QueryField::Title,
             QueryField::Title => QueryField::Genre,
-            QueryField::Genre => QueryField::Plot,
+            QueryField::Genre => QueryField::Any,
+            QueryField::Any => QueryField::Seasons,
         };
         self.query_field = prev_query_field
     }
 
     pub fn get_query(&self) -> &str {
         match self.query_field {
+            QueryField::Any => &self.any_query,
             QueryField::Genre => &self.genre_query,
             QueryField::Title => &self.title_query,
             QueryField::Cast => &self.cast_query,
             QueryField::Country => &self.country_query,
             QueryField::Language => &self.language_query,
             QueryField::Plot => &self.plot_query,
+            QueryField::Director => &self.director_query,
+            QueryField::Writer => &self.writer_query,
+            QueryField::Rating => &self.rating_range,
+            QueryField::Votes => &self.votes_range,
+            QueryField::Year => &self.year_range,
+            QueryField::Runtime => &self.runtime_range,
+            QueryField::Seasons => &self.seasons_range,
         }
     }
 
     pub fn push_onto_query(&mut self, c: char) {
         match self.query_field {
+            QueryField::Any => self.any_query.push(c),
             QueryField::Genre => self.genre_query.push(c),
             QueryField::Title => self.title_query.push(c),
             QueryField::Cast => self.cast_query.push(c),
             QueryField::Country => self.country_query.push(c),
             QueryField::Language => self.language_query.push(c),
             QueryField::Plot => self.plot_query.push(c),
+            QueryField::Director => self.director_query.push(c),
+            QueryField::Writer => self.writer_query.push(c),
+            QueryField::Rating => self.rating_range.push(c),
+            QueryField::Votes => self.votes_range.push(c),
+            QueryField::Year => self.year_range.push(c),
+            QueryField::Runtime => self.runtime_range.push(c),
+            QueryField::Seasons => self.seasons_range.push(c),
         };
     }
 
     pub fn pop_off_query(&mut self) {
         match self.query_field {
+            QueryField::Any => self.any_query.pop(),
             QueryField::Genre => self.genre_query.pop(),
             QueryField::Title => self.title_query.pop(),
             QueryField::Cast => self.cast_query.pop(),
             QueryField::Country => self.country_query.pop(),
             QueryField::Language => self.language_query.pop(),
             QueryField::Plot => self.plot_query.pop(),
+            QueryField::Director => self.director_query.pop(),
+            QueryField::Writer => self.writer_query.pop(),
+            QueryField::Rating => self.rating_range.pop(),
+            QueryField::Votes => self.votes_range.pop(),
+            QueryField::Year => self.year_range.pop(),
+            QueryField::Runtime => self.runtime_range.pop(),
+            QueryField::Seasons => self.seasons_range.pop(),
         };
     }
 
+    pub fn query_error_for(&self, column: &str) -> Option<&QuerySyntaxError> {
+        self.query_error
+            .as_ref()
+            .filter(|err| err.column.as_deref() == Some(column))
+    }
+
+    /// The queries typed into the filter fields by the column they filter.
+    pub fn column_queries(&self) -> Vec<(&'static str, &String)> {
+        vec![
+            (ANY_COLUMN, &self.any_query),
+            (GENRE_COLUMN, &self.genre_query),
+            (TITLE_COLUMN, &self.title_query),
+            (CAST_COLUMN, &self.cast_query),
+            (COUNTRY_COLUMN, &self.country_query),
+            (LANGUAGE_COLUMN, &self.language_query),
+            (PLOT_COLUMN, &self.plot_query),
+            (DIRECTOR_COLUMN, &self.director_query),
+            (WRITER_COLUMN, &self.writer_query),
+        ]
+    }
+
+    fn column_query_mut(&mut self, column: &str) -> Option<&mut String> {
+        match column {
+            ANY_COLUMN => Some(&mut self.any_query),
+            GENRE_COLUMN => Some(&mut self.genre_query),
+            TITLE_COLUMN => Some(&mut self.title_query),
+            CAST_COLUMN => Some(&mut self.cast_query),
+            COUNTRY_COLUMN => Some(&mut self.country_query),
+            LANGUAGE_COLUMN => Some(&mut self.language_query),
+            PLOT_COLUMN => Some(&mut self.plot_query),
+            DIRECTOR_COLUMN => Some(&mut self.director_query),
+            WRITER_COLUMN => Some(&mut self.writer_query),
+            _ => None,
+        }
+    }
+
+    fn range_queries(&self) -> Vec<(RangeColumn, &String)> {
+        vec![
+            (RangeColumn::Rating, &self.rating_range),
+            (RangeColumn::Votes, &self.votes_range),
+            (RangeColumn::Year, &self.year_range),
+            (RangeColumn::Runtime, &self.runtime_range),
+            (RangeColumn::Seasons, &self.seasons_range),
+        ]
+    }
+
+    fn range_query_mut(&mut self, column: RangeColumn) -> &mut String {
+        match column {
+            RangeColumn::Rating => &mut self.rating_range,
+            RangeColumn::Votes => &mut self.votes_range,
+            RangeColumn::Year => &mut self.year_range,
+            RangeColumn::Runtime => &mut self.runtime_range,
+            RangeColumn::Seasons => &mut self.seasons_range,
+        }
+    }
+
+    /// The ranges typed into the range fields, empty ones don't filter.
+    pub fn range_filters(&self) -> Result<Vec<RangeFilter>, QuerySyntaxError> {
+        let mut filters = vec![];
+        for (column, range) in self.range_queries() {
+            filters.extend(RangeFilter::parse(column, range)?);
+        }
+        Ok(filters)
+    }
+
+    /// The filters, type of show, sort and fuzziness as a preset to be saved under the name.
+    pub fn preset(&self, name: &str) -> Preset {
+        let filled = |(column, query): (&str, &String)| {
+            if is_valid_query_filter(query) {
+                Some((column.to_string(), query.to_string()))
+            } else {
+                None
+            }
+        };
+        Preset {
+            name: name.to_string(),
+            filters: self
+                .column_queries()
+                .into_iter()
+                .filter_map(filled)
+                .collect(),
+            ranges: self
+                .range_queries()
+                .into_iter()
+                .map(|(column, range)| (column.name(), range))
+                .filter_map(filled)
+                .collect(),
+            item_type: self.item_type.clone(),
+            sort: self.sort,
+            fuzzy: self.fuzzy,
+        }
+    }
+
+    /// Replaces the filters, type of show, sort and fuzziness with the ones of the preset.
+    pub fn apply_preset(&mut self, preset: &Preset) {
+        self.clear_all_queries();
+        for (column, query) in &preset.filters {
+            if let Some(field) = self.column_query_mut(column) {
+                *field = query.to_string();
+            }
+        }
+        for column in RANGE_COLUMNS {
+            if let Some(range) = preset.ranges.get(column.name()) {
+                *self.range_query_mut(*column) = range.to_string();
+            }
+        }
+        self.item_type = preset.item_type.clone();
+        self.sort = preset.sort;
+        self.fuzzy = preset.fuzzy;
+        self.suspicious_only = false;
+    }
+
     pub fn has_any_query(&self) -> bool {
         vec![
+            &self.any_query,
             &self.genre_query,
             &self.title_query,
             &self.cast_query,
             &self.country_query,
             &self.language_query,
             &self.plot_query,
+            &self.director_query,
+            &self.writer_query,
+            &self.rating_range,
+            &self.votes_range,
+            &self.year_range,
+            &self.runtime_range,
+            &self.seasons_range,
         ]
         .iter()
         .any(|&q| is_valid_query_filter(q))
     }
 
     pub fn clear_all_queries(&mut self) {
+        self.any_query.clear();
         self.genre_query.clear();
         self.title_query.clear();
         self.cast_query.clear();
         self.country_query.clear();
         self.language_query.clear();
         self.plot_query.clear();
+        self.director_query.clear();
+        self.writer_query.clear();
+        self.rating_range.clear();
+        self.votes_range.clear();
+        self.year_range.clear();
+        self.runtime_range.clear();
+        self.seasons_range.clear();
     }
 }
-
@@ -0,0 +1,45 @@
+use crate::data::{build_suspicious_query, Db, ItemType, DEFAULT_PRIOR_VOTES};
+use std::error::Error;
+
+/// Lists synced titles that were likely matched with the wrong OMDb title.
+pub fn audit(db: &Db) -> Result<(), Box<dyn Error>> {
+    // The weighted ratings aren't listed
+    let query = build_suspicious_query(vec![], &[], &ItemType::Both, DEFAULT_PRIOR_VOTES)?;
+    let suspicious = db.get_query_result(&query)?;
+    for row in &suspicious {
+        let lookup = match &row.lookup_title {
+            Some(lookup_title) if lookup_title != &row.title => {
+                format!(" looked up as '{}'", lookup_title)
+            }
+            _ => "".to_string(),
+        };
+        println!(
+            "{:>9} {:>3}% {} ({}){} matched {} '{}' ({}) [{}]",
+            row.id,
+            row.match_confidence.unwrap_or_default(),
+            row.title,
+            row.year,
+            lookup,
+            row.typ,
+            row.omdb_title.as_deref().unwrap_or_default(),
+            row.omdb_year
+                .map(|year| year.to_string())
+                .unwrap_or_default(),
+            row.match_issues.as_deref().unwrap_or_default(),
+        );
+    }
+    println!("Suspicious matches: {}", suspicious.len());
+
+    let unaudited = db
+        .get_synced_rows()?
+        .iter()
+        .filter(|row| row.match_confidence.is_none())
+        .count();
+    if unaudited > 0 {
+        println!(
+            "{} titles were synced before matches were checked, sync them again to audit them.",
+            unaudited
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use crate::data::Db;
+use std::{
+    error::Error,
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    format_duration, print_sync_event, sync_ratings_with_events, RequestBudget, SyncOptions,
+    SyncOutcome,
+};
+
+// How long to wait before looking for titles to sync again once there were none
+const IDLE_WAIT: Duration = Duration::from_secs(60 * 60);
+// Errors like a lost connection tend to persist for a while
+const ERROR_WAIT: Duration = Duration::from_secs(5 * 60);
+// OMDb's clock may be a bit behind ours
+const RESET_MARGIN: Duration = Duration::from_secs(60);
+
+/// Sleeps for the given duration unless the sync is cancelled in the meantime.
+fn wait(duration: Duration, opts: &SyncOptions) {
+    let until = Instant::now() + duration;
+    while !opts.cancel.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now >= until {
+            break;
+        }
+        thread::sleep((until - now).min(Duration::from_secs(1)));
+    }
+}
+
+/// Keeps syncing `opts.limit` titles at a time until the daily requests are used up, then
+/// sleeps until they reset. Runs until `opts.cancel` is set.
+pub fn sync_daemon(db: &Db, api_keys: &[String], opts: SyncOptions) -> Result<(), Box<dyn Error>> {
+    while !opts.cancel.load(Ordering::SeqCst) {
+        let report =
+            sync_ratings_with_events(db, api_keys, opts.clone(), |event| print_sync_event(&event))?;
+        let duration = match report.outcome {
+            SyncOutcome::RateLimited => {
+                RequestBudget::load(db, &api_keys[0])?.resets_in() + RESET_MARGIN
+            }
+            SyncOutcome::Errors => ERROR_WAIT,
+            SyncOutcome::Done if report.results.is_empty() => IDLE_WAIT,
+            SyncOutcome::Done => continue,
+        };
+        if !opts.cancel.load(Ordering::SeqCst) {
+            eprintln!(
+                "Waiting {} before syncing again.",
+                format_duration(duration)
+            );
+            wait(duration, &opts);
+        }
+    }
+    eprintln!("Stopped syncing.");
+    Ok(())
+}
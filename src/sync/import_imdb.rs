@@ -0,0 +1,124 @@
+use crate::{
+    data::unix_now,
+    data::Db,
+    data::JsonRow,
+    data::{imdb_title_type, ImdbBasicsRow, ImdbRatingsRow},
+    data::{normalize_title, normalize_type},
+    RatedRow,
+};
+use csv::{Reader, ReaderBuilder, StringRecord};
+use flate2::read::GzDecoder;
+use std::{collections::HashMap, error::Error, fs::File, path::Path};
+
+const BASICS_FILE: &str = "title.basics.tsv.gz";
+const RATINGS_FILE: &str = "title.ratings.tsv.gz";
+
+fn year_matches(rated_row: &RatedRow, basics: &ImdbBasicsRow) -> bool {
+    match (basics.typ.as_str(), basics.start_year) {
+        ("movie", Some(year)) => year == rated_row.year,
+        // Netflix lists series with the release year of their latest season
+        ("series", Some(start_year)) => {
+            let end_year = basics.end_year.unwrap_or(rated_row.year);
+            start_year <= rated_row.year && rated_row.year <= end_year
+        }
+        _ => false,
+    }
+}
+
+fn open_tsv(dir: &Path, file: &str) -> Result<Reader<GzDecoder<File>>, Box<dyn Error>> {
+    let path = dir.join(file);
+    let file = File::open(&path).map_err(|err| format!("Unable to open {:?}: {}", path, err))?;
+    // IMDb doesn't quote fields, titles with a lone `"` would confuse the reader otherwise
+    Ok(ReaderBuilder::new()
+        .delimiter(b'\t')
+        .quoting(false)
+        .from_reader(GzDecoder::new(file)))
+}
+
+pub fn import_imdb<F>(db: Db, dir: &Path, on_progress: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(usize, usize),
+{
+    let unsynceds = db.get_unsynced_rows()?;
+    eprintln!("Found {} unsynced records", unsynceds.len());
+
+    let mut by_title: HashMap<(String, &'static str), Vec<usize>> = HashMap::new();
+    for (idx, rated_row) in unsynceds.iter().enumerate() {
+        let key = (
+            normalize_title(&rated_row.title),
+            normalize_type(&rated_row.typ),
+        );
+        by_title.entry(key).or_default().push(idx);
+    }
+
+    eprintln!("Matching titles from {}", BASICS_FILE);
+    let mut candidates: HashMap<String, ImdbBasicsRow> = HashMap::new();
+    let mut candidate_ids: Vec<Vec<String>> = vec![vec![]; unsynceds.len()];
+    let mut record = StringRecord::new();
+    let mut rdr = open_tsv(dir, BASICS_FILE)?;
+    while rdr.read_record(&mut record)? {
+        let typ = match imdb_title_type(record.get(1).unwrap_or_default()) {
+            Some(typ) => typ,
+            None => continue,
+        };
+        let key = (normalize_title(record.get(2).unwrap_or_default()), typ);
+        let idxs = match by_title.get(&key) {
+            Some(idxs) => idxs,
+            None => continue,
+        };
+        let basics: ImdbBasicsRow = (&record).into();
+        let mut matched = false;
+        for &idx in idxs {
+            if year_matches(&unsynceds[idx], &basics) {
+                candidate_ids[idx].push(basics.id.clone());
+                matched = true;
+            }
+        }
+        if matched {
+            candidates.insert(basics.id.clone(), basics);
+        }
+    }
+
+    eprintln!("Reading ratings from {}", RATINGS_FILE);
+    let mut ratings: HashMap<String, ImdbRatingsRow> = HashMap::new();
+    let mut rdr = open_tsv(dir, RATINGS_FILE)?;
+    while rdr.read_record(&mut record)? {
+        if candidates.contains_key(record.get(0).unwrap_or_default()) {
+            let row: ImdbRatingsRow = (&record).into();
+            ratings.insert(row.id.clone(), row);
+        }
+    }
+
+    let mut synced_rows: Vec<RatedRow> = vec![];
+    for (idx, ids) in candidate_ids.into_iter().enumerate() {
+        // The same title can be released twice in a year, the more popular one is most likely
+        let best_id = ids
+            .into_iter()
+            .filter(|id| ratings.contains_key(id))
+            .max_by_key(|id| ratings[id].votes.unwrap_or(0));
+        let best_id = match best_id {
+            Some(id) => id,
+            None => continue,
+        };
+
+        let basics = candidates[&best_id].clone();
+        let rating = ratings[&best_id].clone();
+        let json_row: JsonRow = (basics, rating).into();
+        if json_row.is_missing_imdb_data() {
+            continue;
+        }
+
+        let rated_row = &unsynceds[idx];
+        synced_rows.push((rated_row.clone(), json_row, unix_now()).into());
+    }
+    let nimported = synced_rows.len();
+    db.sync_rows(&synced_rows, on_progress)?;
+
+    eprintln!(
+        "Imported ratings for {} of {} unsynced records",
+        nimported,
+        unsynceds.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// The API keys a sync uses one after the other, moving on to the next key once its daily
+/// requests are used up or OMDb reports that it reached its limit.
+pub struct KeyRing {
+    keys: Vec<String>,
+    /// Requests each key has left today, taken before a request is made.
+    remaining: Vec<AtomicU32>,
+    current: AtomicUsize,
+}
+
+impl KeyRing {
+    /// Starts with the first key that has requests left.
+    pub fn new(keys: Vec<String>, remaining: Vec<u32>) -> Self {
+        let first = remaining
+            .iter()
+            .position(|remaining| *remaining > 0)
+            .unwrap_or(keys.len());
+        Self {
+            keys,
+            remaining: remaining.into_iter().map(AtomicU32::new).collect(),
+            current: AtomicUsize::new(first),
+        }
+    }
+
+    /// Takes one of the requests the current key has left and returns its index and the key,
+    /// moving on to the next key if it has none. `None` once all keys are used up.
+    pub fn take_request(&self) -> Option<(usize, &str)> {
+        loop {
+            let idx = self.current.load(Ordering::SeqCst);
+            let remaining = self.remaining.get(idx)?;
+            let taken = remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if taken {
+                return Some((idx, self.keys[idx].as_str()));
+            }
+            self.rotate_from(idx);
+        }
+    }
+
+    /// Gives back a request OMDb didn't count, i.e. because it never responded.
+    pub fn return_request(&self, idx: usize) {
+        self.remaining[idx].fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// OMDb reported that the key at `idx` reached its limit, returns whether there is a key
+    /// left.
+    pub fn exhaust(&self, idx: usize) -> bool {
+        self.remaining[idx].store(0, Ordering::SeqCst);
+        self.rotate_from(idx)
+    }
+
+    // Moves on from the key at `idx` unless another thread did already
+    fn rotate_from(&self, idx: usize) -> bool {
+        self.current
+            .compare_exchange(idx, idx + 1, Ordering::SeqCst, Ordering::SeqCst)
+            .ok();
+        self.current.load(Ordering::SeqCst) < self.keys.len()
+    }
+}
+
+/// Shows enough of a key to tell keys apart without revealing it.
+pub fn mask_key(key: &str) -> String {
+    let prefix: String = key.chars().take(4).collect();
+    format!("{}…", prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_requests_key_by_key() {
+        let keys = KeyRing::new(
+            vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            vec![0, 2, 1],
+        );
+        assert_eq!(keys.take_request(), Some((1, "b")));
+        keys.return_request(1);
+        assert_eq!(keys.take_request(), Some((1, "b")));
+        assert_eq!(keys.take_request(), Some((1, "b")));
+        assert_eq!(keys.take_request(), Some((2, "c")));
+        assert_eq!(keys.take_request(), None);
+    }
+
+    #[test]
+    fn exhausted_by_omdb() {
+        let keys = KeyRing::new(vec!["a".to_string(), "b".to_string()], vec![5, 5]);
+        assert!(keys.exhaust(0));
+        assert_eq!(keys.take_request(), Some((1, "b")));
+        assert!(!keys.exhaust(1));
+        assert_eq!(keys.take_request(), None);
+    }
+}
@@ -1,2 +1,28 @@
+pub use audit::*;
+pub use daemon::*;
+pub use import_imdb::*;
+pub use key_ring::*;
+pub use request_budget::*;
+pub use response_cache::*;
+pub use status::*;
+pub use sync_event::*;
+pub use sync_lock::*;
 pub use sync_ratings::*;
+pub use sync_report::*;
+pub use sync_selection::*;
+pub use throttle::*;
+pub use title_variants::*;
+mod audit;
+mod daemon;
+mod import_imdb;
+mod key_ring;
+mod request_budget;
+mod response_cache;
+mod status;
+mod sync_event;
+mod sync_lock;
 mod sync_ratings;
+mod sync_report;
+mod sync_selection;
+mod throttle;
+mod title_variants;
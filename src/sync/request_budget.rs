@@ -0,0 +1,64 @@
+use crate::data::{unix_now, Db};
+use rusqlite::Result;
+use std::time::Duration;
+
+/// Requests OMDb allows per API key and day on the free plan.
+pub const DAILY_REQUEST_LIMIT: u32 = 1000;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Days since UNIX_EPOCH, OMDb resets its daily limits at midnight UTC.
+pub fn today() -> u32 {
+    (unix_now() as u64 / SECS_PER_DAY) as u32
+}
+
+pub struct RequestBudget {
+    pub api_key: String,
+    pub day: u32,
+    pub used: u32,
+}
+
+impl RequestBudget {
+    pub fn load(db: &Db, api_key: &str) -> Result<Self> {
+        let day = today();
+        let used = db.get_requests_made(api_key, day)?;
+        Ok(Self {
+            api_key: api_key.to_string(),
+            day,
+            used,
+        })
+    }
+
+    pub fn remaining(&self) -> u32 {
+        DAILY_REQUEST_LIMIT.saturating_sub(self.used)
+    }
+
+    pub fn resets_in(&self) -> Duration {
+        let reset_at = (self.day as u64 + 1) * SECS_PER_DAY;
+        Duration::from_secs(reset_at.saturating_sub(unix_now() as u64))
+    }
+
+    /// Records requests made with this key, moving on to the next day's budget if needed.
+    pub fn spend(&mut self, db: &Db, count: u32) -> Result<()> {
+        let day = today();
+        if day != self.day {
+            self.day = day;
+            self.used = 0;
+        }
+        db.add_requests_made(&self.api_key, self.day, count)?;
+        self.used += count;
+        Ok(())
+    }
+
+    /// OMDb told us we're out of requests, so no matter what we counted there are none left.
+    pub fn exhaust(&mut self, db: &Db) -> Result<()> {
+        db.exhaust_requests(&self.api_key, self.day, DAILY_REQUEST_LIMIT)?;
+        self.used = self.used.max(DAILY_REQUEST_LIMIT);
+        Ok(())
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let mins = duration.as_secs() / 60;
+    format!("{}h {}m", mins / 60, mins % 60)
+}
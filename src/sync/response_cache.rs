@@ -0,0 +1,76 @@
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// Caches OMDb responses on disk, one file per request URL.
+/// The URLs must not include the API key so that responses are shared between keys.
+#[derive(Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+// FNV-1a, stable across Rust versions unlike the `DefaultHasher`
+fn hash_url(url: &str) -> u64 {
+    url.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", hash_url(url)))
+    }
+
+    fn is_expired(&self, path: &PathBuf) -> bool {
+        let modified = fs::metadata(path).and_then(|meta| meta.modified());
+        match modified.map(|modified| SystemTime::now().duration_since(modified)) {
+            Ok(Ok(age)) => age > self.ttl,
+            // Modified in the future means someone messed with the clock, just trust it
+            Ok(Err(_)) => false,
+            Err(_) => true,
+        }
+    }
+
+    pub fn get(&self, url: &str) -> Option<String> {
+        let path = self.path(url);
+        if self.is_expired(&path) {
+            return None;
+        }
+        // The first line holds the URL to guard against hash collisions
+        let content = fs::read_to_string(&path).ok()?;
+        let mut parts = content.splitn(2, '\n');
+        match (parts.next(), parts.next()) {
+            (Some(cached_url), Some(body)) if cached_url == url => Some(body.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn put(&self, url: &str, body: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(url), format!("{}\n{}", url, body))
+    }
+
+    /// Removes all cached responses and returns how many there were.
+    pub fn clear(&self) -> io::Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension() == Some(OsStr::new("json")) {
+                fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{
+    data::{format_age, unix_now, Db},
+    OMDB_KEY_VAR,
+};
+use std::error::Error;
+
+use super::{format_duration, mask_key, RequestBudget, DAILY_REQUEST_LIMIT};
+
+pub fn sync_status(db: &Db, api_keys: &[String]) -> Result<(), Box<dyn Error>> {
+    let nsynced = db.get_synced_rows()?.len();
+    let nunsynced = db.get_unsynced_rows()?.len();
+    println!("Rated titles:    {}", nsynced);
+    println!("Unsynced titles: {}", nunsynced);
+    if let (Some(oldest), Some(latest)) = db.get_sync_range()? {
+        let now = unix_now();
+        println!("Last synced:     {}", format_age(latest, now));
+        println!("Oldest rating:   synced {}", format_age(oldest, now));
+    }
+
+    if api_keys.is_empty() {
+        println!("OMDb requests:   no '{}' configured", OMDB_KEY_VAR);
+        return Ok(());
+    }
+
+    let budgets = api_keys
+        .iter()
+        .map(|api_key| RequestBudget::load(db, api_key))
+        .collect::<Result<Vec<RequestBudget>, _>>()?;
+    let remaining: u32 = budgets.iter().map(|budget| budget.remaining()).sum();
+    println!(
+        "OMDb requests:   {} of {} left today",
+        remaining,
+        DAILY_REQUEST_LIMIT * budgets.len() as u32
+    );
+    if budgets.len() > 1 {
+        for budget in &budgets {
+            println!(
+                "  key {:<9}   {} left",
+                mask_key(&budget.api_key),
+                budget.remaining()
+            );
+        }
+    }
+    println!(
+        "Budget resets:   in {} (midnight UTC)",
+        format_duration(budgets[0].resets_in())
+    );
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use crate::RatedRow;
+
+use super::{format_duration, SyncImdbResultType, SyncOutcome};
+
+/// Reports the progress of a sync to whoever runs it, i.e. the CLI or the TUI.
+pub enum SyncEvent {
+    /// About to sync `total` of the `candidates`, which are the unsynced titles unless
+    /// specific titles were selected to `refresh`.
+    Started {
+        candidates: usize,
+        refresh: bool,
+        remaining_requests: u32,
+        total: usize,
+    },
+    /// Today's requests are used up already, so there is nothing to sync.
+    NoRequestsLeft { resets_in: Duration },
+    /// A title that would be synced if this wasn't a dry run.
+    Pending { row: RatedRow, cached: bool },
+    /// A title was looked up, on success `row` holds the updated row.
+    Synced {
+        typ: SyncImdbResultType,
+        row: RatedRow,
+        error: Option<String>,
+    },
+    /// OMDb reported that the `key`th of the `keys` reached its limit, the sync continues
+    /// with the next one.
+    KeyExhausted { key: usize, keys: usize },
+    /// OMDb reported that we ran out of requests with all keys while syncing.
+    RateLimitExceeded { resets_in: Duration },
+    /// Sent by the runner once the sync completed.
+    Finished(SyncOutcome),
+    /// Sent by the runner when the sync aborted due to an error.
+    Failed(String),
+}
+
+pub fn print_sync_event(event: &SyncEvent) {
+    match event {
+        SyncEvent::Started {
+            candidates,
+            refresh,
+            remaining_requests,
+            total,
+        } => {
+            let kind = if *refresh { "matching" } else { "unsynced" };
+            eprintln!(
+                "Found {} {} records, {} requests left for today, syncing {}",
+                candidates, kind, remaining_requests, total
+            )
+        }
+        SyncEvent::NoRequestsLeft { resets_in } => eprintln!(
+            "No requests left for today, the budget resets in {}.",
+            format_duration(*resets_in)
+        ),
+        SyncEvent::Pending { row, cached } => {
+            let note = if *cached { " (cached)" } else { "" };
+            eprintln!("Would sync '{}'{}", row.title, note);
+        }
+        SyncEvent::Synced { typ, row, error } => {
+            if let Some(error) = error {
+                eprintln!("{}", error);
+            }
+            match typ {
+                SyncImdbResultType::Success => eprintln!("Synced '{}' ✓", row.title),
+                // Reported once via SyncEvent::RateLimitExceeded
+                SyncImdbResultType::RateLimitExceeded => {}
+                SyncImdbResultType::UnknownError => eprintln!(
+                    "Encountered unknown error when syncing title '{}'",
+                    row.title
+                ),
+                SyncImdbResultType::NotFound => eprintln!("Could not find title '{}'", row.title),
+                SyncImdbResultType::NoResponse => {
+                    eprintln!("Failed to get response when syncing title '{}'", row.title)
+                }
+                SyncImdbResultType::MissingImdbData => {
+                    eprintln!("Response for '{}' is missing IMDB data", row.title)
+                }
+            }
+        }
+        SyncEvent::KeyExhausted { key, keys } => eprintln!(
+            "OMDb key {} of {} reached its limit, continuing with the next key.",
+            key, keys
+        ),
+        SyncEvent::RateLimitExceeded { resets_in } => eprintln!(
+            "Exceeded rate limit for today. Try again in {}.",
+            format_duration(*resets_in)
+        ),
+        SyncEvent::Finished(_) => {}
+        SyncEvent::Failed(err) => eprintln!("Sync failed: {}", err),
+    }
+}
@@ -0,0 +1,40 @@
+use fs2::FileExt;
+use std::{
+    error::Error,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    process,
+};
+
+/// Makes sure only one sync runs per database, the lock is released when this is dropped
+/// or the process exits, even if it crashed.
+pub struct SyncLock {
+    _file: File,
+}
+
+impl SyncLock {
+    /// Locks the `.sync.lock` file next to the database, failing if another sync holds it.
+    pub fn acquire(db_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut lock_path = db_path.as_os_str().to_owned();
+        lock_path.push(".sync.lock");
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            // Truncating before holding the lock would wipe the pid of the sync that holds it
+            .truncate(false)
+            .open(&lock_path)?;
+        if file.try_lock_exclusive().is_err() {
+            return Err(format!(
+                "Another sync is running for this database, see {:?}",
+                lock_path
+            )
+            .into());
+        }
+        // Only informational, the lock itself is what keeps other syncs out
+        file.set_len(0)?;
+        writeln!(file, "{}", process::id())?;
+        Ok(Self { _file: file })
+    }
+}
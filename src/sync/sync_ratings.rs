@@ -1,5 +1,5 @@
 use crate::{
-    data::secs_since_creation,
+    data::unix_now,
     data::Db,
     data::JsonRow,
     data::{OmdbErrorResponseJson, OmdbSuccessResponseJson},
@@ -7,20 +7,21 @@ use crate::{
 };
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use reqwest::blocking::get;
-use std::{env, error::Error, process};
-
-fn get_api_key() -> String {
-    match env::var("OMDB_KEY") {
-        Err(_) => {
-            eprintln!(
-                "Please add an OMDB  API key as 'OMDB_KEY' to your environment.
-You can obtain it from (http://www.omdbapi.com/)."
-            );
-            process::exit(1)
-        }
-        Ok(key) => key,
-    }
-}
+use serde::Serialize;
+use std::{
+    error::Error,
+    iter,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::mpsc::{channel, Receiver},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use super::{
+    print_sync_event, title_variants, KeyRing, RequestBudget, ResponseCache, SyncEvent,
+    SyncOutcome, SyncReport, SyncSelection, Throttle, DAILY_REQUEST_LIMIT,
+};
 
 fn reached_rate_limit(json: &OmdbErrorResponseJson) -> bool {
     json.Error.contains("limit reached")
@@ -30,18 +31,31 @@ fn not_found(json: &OmdbErrorResponseJson) -> bool {
     json.Error.contains("not found")
 }
 
-fn request_imdb_data_for_title(api_key: &str, title: &str) -> Result<String, Box<dyn Error>> {
-    let uri = format!(
-        "http://www.omdbapi.com/?apikey={}&t={}",
-        api_key,
-        utf8_percent_encode(title, NON_ALPHANUMERIC)
-    );
-    let res = get(&uri).expect("could not get uri");
+const OMDB_URL: &str = "http://www.omdbapi.com/";
+
+// Most results are written as they arrive, but when the writer falls behind this many at most
+// share a transaction
+const WRITE_BATCH_SIZE: usize = 50;
+
+fn title_query(title: &str) -> String {
+    format!("t={}", utf8_percent_encode(title, NON_ALPHANUMERIC))
+}
+
+// Without the API key, this is what we cache responses by
+fn omdb_url(query: &str) -> String {
+    format!("{}?{}", OMDB_URL, query)
+}
+
+fn request_imdb_data(api_key: &str, query: &str) -> Result<String, Box<dyn Error>> {
+    let uri = format!("{}?apikey={}&{}", OMDB_URL, api_key, query);
+    let res = get(&uri)?;
     let txt = res.text()?;
     Ok(txt)
 }
 
-enum SyncImdbResultType {
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncImdbResultType {
     Success,
     RateLimitExceeded,
     MissingImdbData,
@@ -52,149 +66,538 @@ enum SyncImdbResultType {
 struct SyncImdbResult {
     typ: SyncImdbResultType,
     row: Option<JsonRow>,
+    /// Key index of each request OMDb counted against our budget, empty if all responses came
+    /// from the cache.
+    requests: Vec<usize>,
+    /// Indexes of the keys OMDb reported as having reached their limit.
+    exhausted_keys: Vec<usize>,
+    /// The title variant that produced this result.
+    lookup_title: Option<String>,
+    error: Option<String>,
+}
+
+impl SyncImdbResult {
+    // Errors may go away when retrying, everything else OMDb will tell us again
+    fn is_cacheable(&self) -> bool {
+        match self.typ {
+            SyncImdbResultType::Success
+            | SyncImdbResultType::MissingImdbData
+            | SyncImdbResultType::NotFound => true,
+            SyncImdbResultType::RateLimitExceeded
+            | SyncImdbResultType::NoResponse
+            | SyncImdbResultType::UnknownError => false,
+        }
+    }
+}
+
+fn cached_variant(cache: Option<&ResponseCache>, variant: &str) -> Option<SyncImdbResult> {
+    let text = cache?.get(&omdb_url(&title_query(variant)))?;
+    let result = sync_result_from_response(&text);
+    if result.is_cacheable() {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+/// The result of looking up all title variants, `None` unless it can be told from the cache alone.
+fn cached_imdb_title(cache: Option<&ResponseCache>, title: &str) -> Option<SyncImdbResult> {
+    let mut result = None;
+    for variant in title_variants(title) {
+        let variant_result = cached_variant(cache, &variant)?;
+        let not_found = variant_result.typ == SyncImdbResultType::NotFound;
+        result = Some(SyncImdbResult {
+            lookup_title: Some(variant),
+            ..variant_result
+        });
+        if !not_found {
+            break;
+        }
+    }
+    result
+}
+
+/// Tries the title variants until OMDb finds one, taking responses from the cache when possible.
+/// Each variant requested costs one of the keys' requests, once they are used up the remaining
+/// variants are left for another day and the title is reported as rate limited.
+/// Returns `None` if the sync was stopped before a request for this title was made.
+fn lookup_imdb_title<F>(
+    keys: &KeyRing,
+    title: &str,
+    cache: Option<&ResponseCache>,
+    throttle: &Throttle,
+    stop: &AtomicBool,
+    request: F,
+) -> Option<SyncImdbResult>
+where
+    F: Fn(&str, &str) -> SyncImdbResult,
+{
+    let mut requests = vec![];
+    let mut exhausted_keys = vec![];
+    let mut result = None;
+    for variant in title_variants(title) {
+        let variant_result = match cached_variant(cache, &variant) {
+            Some(result) => result,
+            None => loop {
+                throttle.wait();
+                // Another worker may have used up the last key while we were waiting, once we
+                // made requests for this title we finish it so that they are accounted for
+                if requests.is_empty() && exhausted_keys.is_empty() && stop.load(Ordering::SeqCst) {
+                    return None;
+                }
+                let (key_idx, api_key) = match keys.take_request() {
+                    Some(key) => key,
+                    None => break rate_limit_exceeded(),
+                };
+                let result = request(api_key, &variant);
+                match result.typ {
+                    SyncImdbResultType::RateLimitExceeded => {
+                        exhausted_keys.push(key_idx);
+                        if keys.exhaust(key_idx) {
+                            continue;
+                        }
+                    }
+                    SyncImdbResultType::NoResponse => keys.return_request(key_idx),
+                    _ => requests.push(key_idx),
+                }
+                break result;
+            },
+        };
+        let not_found = variant_result.typ == SyncImdbResultType::NotFound;
+        result = Some(SyncImdbResult {
+            lookup_title: Some(variant),
+            ..variant_result
+        });
+        if !not_found {
+            break;
+        }
+    }
+    result.map(|result| SyncImdbResult {
+        requests,
+        exhausted_keys,
+        ..result
+    })
 }
 
-fn sync_imdb_title(api_key: &str, title: &str) -> SyncImdbResult {
-    match request_imdb_data_for_title(api_key, title) {
+fn rate_limit_exceeded() -> SyncImdbResult {
+    SyncImdbResult {
+        typ: SyncImdbResultType::RateLimitExceeded,
+        row: None,
+        requests: vec![],
+        exhausted_keys: vec![],
+        lookup_title: None,
+        error: None,
+    }
+}
+
+fn sync_imdb_title(api_key: &str, title: &str, cache: Option<&ResponseCache>) -> SyncImdbResult {
+    let query = title_query(title);
+    match request_imdb_data(api_key, &query) {
         Ok(text) => {
-            let omdb_json: Option<OmdbSuccessResponseJson> =
-                serde_json::from_str(&text).unwrap_or(None);
-            match omdb_json {
+            let result = sync_result_from_response(&text);
+            if let Some(cache) = cache {
+                if result.is_cacheable() {
+                    // Failing to cache only costs us the same request again next time
+                    cache.put(&omdb_url(&query), &text).ok();
+                }
+            }
+            result
+        }
+        // Didn't get a response at all
+        Err(err) => SyncImdbResult {
+            typ: SyncImdbResultType::NoResponse,
+            row: None,
+            requests: vec![],
+            exhausted_keys: vec![],
+            lookup_title: None,
+            error: Some(format!("No response {}", err)),
+        },
+    }
+}
+
+fn sync_result_from_response(text: &str) -> SyncImdbResult {
+    let omdb_json: Option<OmdbSuccessResponseJson> = serde_json::from_str(text).unwrap_or(None);
+    match omdb_json {
+        Some(json) => {
+            let row: JsonRow = json.into();
+            if row.is_missing_imdb_data() {
+                SyncImdbResult {
+                    typ: SyncImdbResultType::MissingImdbData,
+                    row: None,
+                    requests: vec![],
+                    exhausted_keys: vec![],
+                    lookup_title: None,
+                    error: None,
+                }
+            } else {
+                SyncImdbResult {
+                    typ: SyncImdbResultType::Success,
+                    row: Some(row),
+                    requests: vec![],
+                    exhausted_keys: vec![],
+                    lookup_title: None,
+                    error: None,
+                }
+            }
+        }
+        None => {
+            let error_response_json: Option<OmdbErrorResponseJson> =
+                serde_json::from_str(text).unwrap_or(None);
+            match error_response_json {
                 Some(json) => {
-                    let row: JsonRow = json.into();
-                    if row.is_missing_imdb_data() {
+                    if reached_rate_limit(&json) {
                         SyncImdbResult {
-                            typ: SyncImdbResultType::MissingImdbData,
+                            typ: SyncImdbResultType::RateLimitExceeded,
                             row: None,
+                            requests: vec![],
+                            exhausted_keys: vec![],
+                            lookup_title: None,
+                            error: None,
                         }
-                    } else {
+                    } else if not_found(&json) {
                         SyncImdbResult {
-                            typ: SyncImdbResultType::Success,
-                            row: Some(row),
-                        }
-                    }
-                }
-                None => {
-                    let error_response_json: Option<OmdbErrorResponseJson> =
-                        serde_json::from_str(&text).unwrap_or(None);
-                    match error_response_json {
-                        Some(json) => {
-                            if reached_rate_limit(&json) {
-                                SyncImdbResult {
-                                    typ: SyncImdbResultType::RateLimitExceeded,
-                                    row: None,
-                                }
-                            } else if not_found(&json) {
-                                SyncImdbResult {
-                                    typ: SyncImdbResultType::NotFound,
-                                    row: None,
-                                }
-                            } else {
-                                eprintln!("Error: {}", json.Error);
-                                SyncImdbResult {
-                                    typ: SyncImdbResultType::UnknownError,
-                                    row: None,
-                                }
-                            }
+                            typ: SyncImdbResultType::NotFound,
+                            row: None,
+                            requests: vec![],
+                            exhausted_keys: vec![],
+                            lookup_title: None,
+                            error: None,
                         }
-                        None => {
-                            eprintln!("Response: {}", text);
-                            SyncImdbResult {
-                                typ: SyncImdbResultType::UnknownError,
-                                row: None,
-                            }
+                    } else {
+                        SyncImdbResult {
+                            typ: SyncImdbResultType::UnknownError,
+                            row: None,
+                            requests: vec![],
+                            exhausted_keys: vec![],
+                            lookup_title: None,
+                            error: Some(format!("Error: {}", json.Error)),
                         }
                     }
                 }
+                None => SyncImdbResult {
+                    typ: SyncImdbResultType::UnknownError,
+                    row: None,
+                    requests: vec![],
+                    exhausted_keys: vec![],
+                    lookup_title: None,
+                    error: Some(format!("Response: {}", text)),
+                },
             }
         }
-        // Didn't get a response at all
-        Err(err) => {
-            eprintln!("No response {}", err);
-            SyncImdbResult {
-                typ: SyncImdbResultType::NoResponse,
-                row: None,
-            }
+    }
+}
+
+#[derive(Clone)]
+pub struct SyncOptions {
+    /// Maximum number of titles to sync.
+    pub limit: usize,
+    /// Number of requests in flight at the same time.
+    pub concurrency: usize,
+    /// Maximum number of requests started per second, `0` for no limit.
+    pub requests_per_second: f64,
+    /// Where to look up responses before making requests, `None` to always request.
+    pub cache: Option<ResponseCache>,
+    /// Only report which titles would be synced without making any requests.
+    pub dry_run: bool,
+    /// Titles to sync, by default the ones not synced yet.
+    pub selection: SyncSelection,
+    /// Sync titles again that were synced longer ago than this, after the unsynced ones.
+    pub stale_after: Option<Duration>,
+    /// Set to stop the sync once the titles in flight are done, i.e. on Ctrl-C.
+    pub cancel: Arc<AtomicBool>,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            limit: DAILY_REQUEST_LIMIT as usize,
+            concurrency: 4,
+            requests_per_second: 10.0,
+            cache: None,
+            dry_run: false,
+            selection: SyncSelection::default(),
+            stale_after: None,
+            cancel: Arc::new(AtomicBool::new(false)),
         }
     }
 }
 
-pub fn sync_ratings(db: Db, limit: usize) -> Result<(), Box<dyn Error>> {
-    let unsynceds = db.get_unsynced_rows()?;
-    let api_key = get_api_key();
-    let nunsynced = unsynceds.len();
-    let amount_to_sync = limit.min(nunsynced);
-    eprintln!(
-        "Found {} unsynced records, syncing {}",
-        nunsynced, amount_to_sync
-    );
-    let first_sync = true;
-    let mut _exceeded_limit = false;
-    for i in 0..amount_to_sync {
-        let rated_row = unsynceds.get(i).unwrap();
-        eprint!("Syncing '{}'", rated_row.title);
-        match sync_imdb_title(&api_key, &rated_row.title) {
-            SyncImdbResult {
-                typ: SyncImdbResultType::Success,
-                row,
-            } => {
-                assert!(row.is_some(), "row should be set for successful sync");
-                let json_row = row.unwrap();
-                let synced_rated_row: RatedRow =
-                    (rated_row.clone(), json_row, secs_since_creation()).into();
-                db.sync_row(&synced_rated_row)?;
-                eprintln!(" ✓");
-            }
-            SyncImdbResult {
-                typ: SyncImdbResultType::RateLimitExceeded,
-                ..
-            } => {
-                eprintln!("\nExceeded rate limit for today. Try again in 24 hours.");
-                _exceeded_limit = true;
-                break;
-            }
-            SyncImdbResult {
-                typ: SyncImdbResultType::UnknownError,
-                ..
-            } => {
-                eprintln!(
-                    "\nEncountered unknown error when syncing title '{}'",
-                    rated_row.title
-                );
-            }
-            SyncImdbResult {
-                typ: SyncImdbResultType::NotFound,
-                ..
-            } => {
-                eprintln!("\nCould not find title '{}'", rated_row.title);
-                if first_sync {
-                    db.delete_row(rated_row.id)?;
+fn spawn_sync_workers(
+    keys: KeyRing,
+    rows: Vec<RatedRow>,
+    throttle: Throttle,
+    opts: SyncOptions,
+) -> (Receiver<(RatedRow, SyncImdbResult)>, Vec<JoinHandle<()>>) {
+    let queue = Arc::new(Mutex::new(rows.into_iter()));
+    let keys = Arc::new(keys);
+    let throttle = Arc::new(throttle);
+    let cache = Arc::new(opts.cache);
+    let stop = Arc::new(AtomicBool::new(false));
+    let cancel = opts.cancel;
+    let (tx, rx) = channel();
+
+    let workers = (0..opts.concurrency.max(1))
+        .map(|_| {
+            let keys = keys.clone();
+            let queue = queue.clone();
+            let throttle = throttle.clone();
+            let cache = cache.clone();
+            let stop = stop.clone();
+            let cancel = cancel.clone();
+            let tx = tx.clone();
+            thread::spawn(move || loop {
+                if stop.load(Ordering::SeqCst) || cancel.load(Ordering::SeqCst) {
+                    break;
                 }
+                let rated_row = match queue.lock().unwrap().next() {
+                    Some(row) => row,
+                    None => break,
+                };
+                let cache = cache.as_ref().as_ref();
+                let request =
+                    |api_key: &str, variant: &str| sync_imdb_title(api_key, variant, cache);
+                let result = match lookup_imdb_title(
+                    &keys,
+                    &rated_row.title,
+                    cache,
+                    &throttle,
+                    &stop,
+                    request,
+                ) {
+                    Some(result) => result,
+                    None => break,
+                };
+                if let SyncImdbResultType::RateLimitExceeded = result.typ {
+                    stop.store(true, Ordering::SeqCst);
+                }
+                if tx.send((rated_row, result)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+
+    (rx, workers)
+}
+
+pub fn sync_ratings(
+    db: Db,
+    api_keys: &[String],
+    opts: SyncOptions,
+) -> Result<SyncReport, Box<dyn Error>> {
+    sync_ratings_with_events(&db, api_keys, opts, |event| print_sync_event(&event))
+}
+
+/// Syncs with the given keys one after the other, so that one sync can use their combined
+/// daily requests.
+pub fn sync_ratings_with_events<F>(
+    db: &Db,
+    api_keys: &[String],
+    opts: SyncOptions,
+    mut on_event: F,
+) -> Result<SyncReport, Box<dyn Error>>
+where
+    F: FnMut(SyncEvent),
+{
+    let throttle = Throttle::new(opts.requests_per_second)?;
+    let refresh = !opts.selection.is_empty();
+    let mut candidates = if refresh {
+        db.get_query_result(&opts.selection.query()?)?
+    } else {
+        db.get_unsynced_rows()?
+    };
+    if let (false, Some(stale_after)) = (refresh, opts.stale_after) {
+        let synced_before = unix_now() - stale_after.as_secs() as i64;
+        candidates.extend(db.get_stale_rows(synced_before)?);
+    }
+    let mut budgets = api_keys
+        .iter()
+        .map(|api_key| RequestBudget::load(db, api_key))
+        .collect::<Result<Vec<RequestBudget>, _>>()?;
+    let mut report = SyncReport::new(opts.dry_run);
+
+    // Cached responses don't cost us a request. Titles OMDb only finds by another variant cost
+    // more than one, the lookups account for those and stop once the keys are used up.
+    let ncandidates = candidates.len();
+    let remaining: u32 = budgets.iter().map(|budget| budget.remaining()).sum();
+    let remaining = remaining as usize;
+    let mut nrequests = 0;
+    let rows: Vec<RatedRow> = candidates
+        .into_iter()
+        .take_while(|row| {
+            if cached_imdb_title(opts.cache.as_ref(), &row.title).is_none() {
+                nrequests += 1;
             }
-            SyncImdbResult {
-                typ: SyncImdbResultType::NoResponse,
-                ..
-            } => {
-                eprintln!(
-                    "\nFailed to get response when syncing title '{}'",
-                    rated_row.title
-                );
-            }
-            SyncImdbResult {
-                typ: SyncImdbResultType::MissingImdbData,
-                ..
-            } => {
-                eprintln!("\nResponse for '{}' is missing IMDB data", rated_row.title);
-                if first_sync {
-                    db.delete_row(rated_row.id)?;
+            nrequests <= remaining
+        })
+        .take(opts.limit)
+        .collect();
+
+    // All keys share the same day, so they reset at the same time
+    let resets_in = budgets
+        .first()
+        .map(|budget| budget.resets_in())
+        .unwrap_or_default();
+    if rows.is_empty() && remaining == 0 {
+        on_event(SyncEvent::NoRequestsLeft { resets_in });
+        report.outcome = SyncOutcome::RateLimited;
+        return Ok(report);
+    }
+    on_event(SyncEvent::Started {
+        candidates: ncandidates,
+        refresh,
+        remaining_requests: remaining as u32,
+        total: rows.len(),
+    });
+
+    if opts.dry_run {
+        for row in rows {
+            let cached = cached_imdb_title(opts.cache.as_ref(), &row.title).is_some();
+            report.pending.push((&row).into());
+            on_event(SyncEvent::Pending { row, cached });
+        }
+        return Ok(report);
+    }
+    let mut exceeded_limit = false;
+
+    // Keys we know to be used up are skipped right away
+    let keys = KeyRing::new(
+        api_keys.to_vec(),
+        budgets.iter().map(|budget| budget.remaining()).collect(),
+    );
+    let (results, workers) = spawn_sync_workers(keys, rows, throttle, opts);
+
+    // All database writes happen here while the workers only make requests. Results that
+    // arrived in the meantime are written together in one transaction.
+    while let Ok(result) = results.recv() {
+        let batch: Vec<(RatedRow, SyncImdbResult)> = iter::once(result)
+            .chain(results.try_iter().take(WRITE_BATCH_SIZE - 1))
+            .collect();
+        let synced = db.in_transaction(|| {
+            let mut synced = Vec::with_capacity(batch.len());
+            for (rated_row, result) in batch {
+                // Requests without a response or past the limit aren't counted, the latter are
+                // accounted for by exhausting the key's whole budget
+                for key_idx in &result.requests {
+                    let budget = &mut budgets[*key_idx];
+                    let had_requests = budget.remaining() > 0;
+                    budget.spend(db, 1)?;
+                    if had_requests && budget.remaining() == 0 && key_idx + 1 < budgets.len() {
+                        on_event(SyncEvent::KeyExhausted {
+                            key: key_idx + 1,
+                            keys: budgets.len(),
+                        });
+                    }
                 }
+                report.requests += result.requests.len() as u32;
+                for key_idx in &result.exhausted_keys {
+                    // Requests in flight on other workers will report it as well
+                    if budgets[*key_idx].remaining() > 0 {
+                        budgets[*key_idx].exhaust(db)?;
+                        if key_idx + 1 < budgets.len() {
+                            on_event(SyncEvent::KeyExhausted {
+                                key: key_idx + 1,
+                                keys: budgets.len(),
+                            });
+                        }
+                    }
+                }
+                report.add(result.typ, &rated_row);
+                let SyncImdbResult {
+                    typ,
+                    row,
+                    lookup_title,
+                    error,
+                    ..
+                } = result;
+                let row = match typ {
+                    SyncImdbResultType::Success => {
+                        assert!(row.is_some(), "row should be set for successful sync");
+                        let json_row = row.unwrap();
+                        // The match is assessed against the variant that found it
+                        let rated_row = RatedRow {
+                            lookup_title,
+                            ..rated_row
+                        };
+                        let synced_rated_row: RatedRow = (rated_row, json_row, unix_now()).into();
+                        db.sync_row(&synced_rated_row)?;
+                        synced_rated_row
+                    }
+                    SyncImdbResultType::RateLimitExceeded => {
+                        // Only reported once all keys are used up
+                        if !exceeded_limit {
+                            on_event(SyncEvent::RateLimitExceeded { resets_in });
+                        }
+                        exceeded_limit = true;
+                        rated_row
+                    }
+                    SyncImdbResultType::NotFound | SyncImdbResultType::MissingImdbData => {
+                        // Titles that synced before keep their data, but aren't stale anymore
+                        if rated_row.last_sync.is_none() {
+                            db.delete_row(rated_row.id)?;
+                        } else {
+                            db.touch_row(rated_row.id, unix_now())?;
+                        }
+                        rated_row
+                    }
+                    SyncImdbResultType::UnknownError | SyncImdbResultType::NoResponse => rated_row,
+                };
+                synced.push((typ, row, error));
             }
+            Ok(synced)
+        })?;
+        for (typ, row, error) in synced {
+            on_event(SyncEvent::Synced { typ, row, error });
         }
     }
 
-    // TODO: if after all rows synced at least once we still have requests remaining
-    // 1. sort rows by sync date
-    // 2. sync rows until no more requests remaining
+    for worker in workers {
+        worker.join().expect("sync worker panicked");
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const NOT_FOUND: &str = r#"{"Response":"False","Error":"Movie not found!"}"#;
+
+    fn lookup(keys: &KeyRing, title: &str, made: &Cell<u32>) -> SyncImdbResult {
+        let throttle = Throttle::new(0.0).unwrap();
+        let stop = AtomicBool::new(false);
+        lookup_imdb_title(keys, title, None, &throttle, &stop, |_, _| {
+            made.set(made.get() + 1);
+            sync_result_from_response(NOT_FOUND)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn each_variant_costs_a_request() {
+        let title = "The Amélie & Nino Story";
+        let made = Cell::new(0);
+        let keys = KeyRing::new(vec!["a".to_string(), "b".to_string()], vec![3, 10]);
+        let result = lookup(&keys, title, &made);
+        assert_eq!(made.get(), 4);
+        assert_eq!(result.typ, SyncImdbResultType::NotFound);
+        assert_eq!(result.requests, vec![0, 0, 0, 1]);
+        assert_eq!(
+            result.lookup_title.as_deref(),
+            Some("Amelie and Nino Story")
+        );
+    }
 
-    Ok(())
+    #[test]
+    fn stops_trying_variants_once_keys_are_used_up() {
+        let made = Cell::new(0);
+        let keys = KeyRing::new(vec!["a".to_string()], vec![2]);
+        let result = lookup(&keys, "The Amélie & Nino Story", &made);
+        assert_eq!(made.get(), 2);
+        assert_eq!(result.typ, SyncImdbResultType::RateLimitExceeded);
+        assert_eq!(result.requests, vec![0, 0]);
+        assert_eq!(keys.take_request(), None);
+    }
 }
@@ -0,0 +1,88 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::RatedRow;
+
+use super::SyncImdbResultType;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncOutcome {
+    Done,
+    RateLimited,
+    Errors,
+}
+
+impl SyncOutcome {
+    /// Exit code for the CLI, `1` is taken by errors that abort the sync altogether.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SyncOutcome::Done => 0,
+            SyncOutcome::RateLimited => 2,
+            SyncOutcome::Errors => 3,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ReportedTitle {
+    pub id: u32,
+    pub title: String,
+    /// When the title was synced before as Unix timestamp, missing for titles never synced.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_sync: Option<i64>,
+}
+
+impl From<&RatedRow> for ReportedTitle {
+    fn from(row: &RatedRow) -> Self {
+        Self {
+            id: row.id,
+            title: row.title.clone(),
+            last_sync: row.last_sync,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ReportedResults {
+    pub count: usize,
+    pub titles: Vec<ReportedTitle>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SyncReport {
+    pub outcome: SyncOutcome,
+    pub dry_run: bool,
+    /// Requests made to OMDb, responses taken from the cache aren't counted.
+    pub requests: u32,
+    /// Titles that would have been synced in a dry run.
+    pub pending: Vec<ReportedTitle>,
+    pub results: BTreeMap<SyncImdbResultType, ReportedResults>,
+}
+
+impl SyncReport {
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            outcome: SyncOutcome::Done,
+            dry_run,
+            requests: 0,
+            pending: vec![],
+            results: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, typ: SyncImdbResultType, row: &RatedRow) {
+        let results = self.results.entry(typ).or_default();
+        results.count += 1;
+        results.titles.push(row.into());
+
+        // Errors are worse than running out of requests which is expected to happen daily
+        self.outcome = match (self.outcome, typ) {
+            (_, SyncImdbResultType::UnknownError) | (_, SyncImdbResultType::NoResponse) => {
+                SyncOutcome::Errors
+            }
+            (SyncOutcome::Done, SyncImdbResultType::RateLimitExceeded) => SyncOutcome::RateLimited,
+            (outcome, _) => outcome,
+        };
+    }
+}
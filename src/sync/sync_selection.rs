@@ -0,0 +1,62 @@
+use crate::data::{
+    build_sync_query, ColumnFilter, ItemType, Query, QuerySyntaxError, GENRE_COLUMN, TITLE_COLUMN,
+};
+
+/// Picks the titles to sync instead of the unsynced ones, titles synced before are synced again.
+#[derive(Clone, Default)]
+pub struct SyncSelection {
+    pub ids: Vec<u32>,
+    pub title: Option<String>,
+    pub genre: Option<String>,
+    pub item_type: Option<ItemType>,
+}
+
+impl SyncSelection {
+    /// Whether nothing is selected, which `--type both` on its own doesn't change as it matches
+    /// every title and would sync the whole catalog again.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+            && self.title.is_none()
+            && self.genre.is_none()
+            && matches!(self.item_type, None | Some(ItemType::Both))
+    }
+
+    pub fn query(&self) -> Result<Query, QuerySyntaxError> {
+        let mut filters: Vec<ColumnFilter> = vec![];
+        if let Some(title) = &self.title {
+            filters.push((TITLE_COLUMN, title).into());
+        }
+        if let Some(genre) = &self.genre {
+            filters.push((GENRE_COLUMN, genre).into());
+        }
+        let item_type = self.item_type.clone().unwrap_or(ItemType::Both);
+        build_sync_query(&self.ids, filters, &item_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_types_alone_select_nothing() {
+        let selection = SyncSelection {
+            item_type: Some(ItemType::Both),
+            ..Default::default()
+        };
+        assert!(selection.is_empty());
+
+        let selection = SyncSelection {
+            item_type: Some(ItemType::Series),
+            ..Default::default()
+        };
+        assert!(!selection.is_empty());
+
+        let selection = SyncSelection {
+            genre: Some("drama".to_string()),
+            item_type: Some(ItemType::Both),
+            ..Default::default()
+        };
+        assert!(!selection.is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Spaces out requests made from multiple threads so that no more than
+/// `requests_per_second` are started in any second.
+pub struct Throttle {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+/// Time between the start of two requests, none if `requests_per_second` is 0. Fails for rates
+/// that are negative, not a number or so small that the time doesn't fit into a `Duration`.
+pub fn request_interval(requests_per_second: f64) -> Result<Duration, String> {
+    if requests_per_second == 0.0 {
+        return Ok(Duration::from_secs(0));
+    }
+    let secs = 1.0 / requests_per_second;
+    // `from_secs_f64` panics for anything that isn't a finite, positive u64 of seconds
+    if requests_per_second.is_finite() && secs > 0.0 && secs < u64::MAX as f64 {
+        Ok(Duration::from_secs_f64(secs))
+    } else {
+        Err("requests per second need to be a positive number, i.e. 10, or 0 for no limit".into())
+    }
+}
+
+impl Throttle {
+    pub fn new(requests_per_second: f64) -> Result<Self, String> {
+        Ok(Self {
+            interval: request_interval(requests_per_second)?,
+            next_slot: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Blocks until the calling thread may make its request.
+    pub fn wait(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        let now = Instant::now();
+        if slot > now {
+            thread::sleep(slot - now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals() {
+        assert_eq!(request_interval(0.0), Ok(Duration::from_secs(0)));
+        assert_eq!(request_interval(4.0), Ok(Duration::from_millis(250)));
+        assert!(request_interval(1e-300).is_err());
+        assert!(request_interval(-1.0).is_err());
+        assert!(request_interval(f64::NAN).is_err());
+        assert!(request_interval(f64::INFINITY).is_err());
+    }
+}
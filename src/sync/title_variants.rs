@@ -0,0 +1,119 @@
+use crate::data::strip_diacritics;
+
+const LEADING_ARTICLES: [&str; 3] = ["the ", "a ", "an "];
+const SEASON_SEPARATORS: [&str; 4] = [":", " -", "(", ","];
+
+// "Norm of the North: Season 2", "Narcos - Season 3" or "Ozark (Season 1)"
+fn strip_season_suffix(title: &str) -> String {
+    let lower = title.to_ascii_lowercase();
+    match lower.rfind("season ") {
+        Some(idx) => {
+            let number = lower[idx + "season ".len()..].trim_end_matches(')').trim();
+            if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+                return title.to_string();
+            }
+            let head = title[..idx].trim_end();
+            SEASON_SEPARATORS
+                .iter()
+                .fold(head, |head, separator| head.trim_end_matches(separator))
+                .trim_end()
+                .to_string()
+        }
+        None => title.to_string(),
+    }
+}
+
+fn replace_ampersand(title: &str) -> String {
+    title.replace(" & ", " and ").replace("&", " and ")
+}
+
+fn strip_leading_article(title: &str) -> String {
+    let lower = title.to_ascii_lowercase();
+    match LEADING_ARTICLES
+        .iter()
+        .find(|article| lower.starts_with(*article))
+    {
+        Some(article) => title[article.len()..].trim_start().to_string(),
+        None => title.to_string(),
+    }
+}
+
+// "Norm of the North: King Sized Adventure" becomes "Norm of the North"
+fn strip_subtitle(title: &str) -> String {
+    match title.find(':') {
+        Some(idx) if idx > 0 => title[..idx].trim_end().to_string(),
+        _ => title.to_string(),
+    }
+}
+
+/// Titles to look up on OMDb in order of decreasing specificity, the first one being the
+/// Netflix title itself. Each variant builds on the ones before it and duplicates are dropped.
+pub fn title_variants(title: &str) -> Vec<String> {
+    let title = title.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let without_season = strip_season_suffix(&title);
+    let with_and = replace_ampersand(&without_season);
+    let ascii = strip_diacritics(&with_and);
+    let without_article = strip_leading_article(&ascii);
+    let without_subtitle = strip_subtitle(&ascii);
+    let main_title = strip_leading_article(&without_subtitle);
+
+    let mut variants: Vec<String> = vec![];
+    for variant in [
+        title,
+        without_season,
+        with_and,
+        ascii,
+        without_article,
+        without_subtitle,
+        main_title,
+    ]
+    .iter()
+    {
+        if !variant.is_empty() && !variants.contains(variant) {
+            variants.push(variant.clone());
+        }
+    }
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subtitle() {
+        assert_eq!(
+            title_variants("Norm of the North: King Sized Adventure"),
+            vec![
+                "Norm of the North: King Sized Adventure",
+                "Norm of the North"
+            ]
+        );
+    }
+
+    #[test]
+    fn season_suffix() {
+        assert_eq!(
+            title_variants("Narcos: Season 2"),
+            vec!["Narcos: Season 2", "Narcos"]
+        );
+        assert_eq!(
+            title_variants("Ozark (Season 1)"),
+            vec!["Ozark (Season 1)", "Ozark"]
+        );
+        assert_eq!(title_variants("Seasons"), vec!["Seasons"]);
+    }
+
+    #[test]
+    fn ampersand_diacritics_and_article() {
+        assert_eq!(
+            title_variants("The Amélie & Nino Story"),
+            vec![
+                "The Amélie & Nino Story",
+                "The Amélie and Nino Story",
+                "The Amelie and Nino Story",
+                "Amelie and Nino Story",
+            ]
+        );
+    }
+}
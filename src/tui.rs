@@ -1,15 +1,18 @@
 use super::{
-    data::build_sorted_filtered_query, data::build_sorted_query, data::Db, data::CAST_COLUMN,
-    data::COUNTRY_COLUMN, data::GENRE_COLUMN, data::LANGUAGE_COLUMN, data::PLOT_COLUMN,
-    data::TITLE_COLUMN, render::maybe_render_item_details, render::render_admin,
-    render::render_log, render::render_rows_summary, render::App, render::Log,
+    data::build_sorted_filtered_query, data::build_sorted_query, data::build_suspicious_query,
+    data::matched_fields, data::ColumnFilter, data::DatabaseInfo, data::Db, data::ItemType,
+    data::Sort, format_duration, render::maybe_render_item_details, render::render_admin,
+    render::render_log, render::render_preset_popup, render::render_rows_summary, render::App,
+    render::Log, render::PresetPopup, render::StatefulList, render::SyncProgress,
+    sync_ratings_with_events, SyncEvent, SyncImdbResultType, SyncLock, SyncOptions, SyncOutcome,
+    SyncSelection,
 };
 use crossterm::{
     event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers},
     terminal::disable_raw_mode,
     terminal::enable_raw_mode,
 };
-use std::{error::Error, io::stdout, process, time::Duration};
+use std::{error::Error, io::stdout, process, sync::mpsc, thread, time::Duration};
 use tui::{
     backend::Backend, backend::CrosstermBackend, layout::Constraint, layout::Direction,
     layout::Layout, layout::Rect, Frame, Terminal,
@@ -17,11 +20,19 @@ use tui::{
 
 const PAGE_MARGIN_HEIGHT: i32 = 3;
 
+/// What's needed to sync ratings on a background thread while the TUI is up.
+#[derive(Clone)]
+pub struct BackgroundSync {
+    pub db_info: DatabaseInfo,
+    pub api_keys: Vec<String>,
+    pub opts: SyncOptions,
+}
+
 fn render_summary_and_admin<B>(f: &mut Frame<B>, app: &mut App, container: Rect) -> (Rect, Rect)
 where
     B: Backend,
 {
-    let items = render_rows_summary(&app.items.items);
+    let items = render_rows_summary(&app.items.items, &app.matched_fields);
 
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
@@ -39,40 +50,64 @@ where
 }
 
 fn exec_query(app: &mut App, db: &Db) -> Result<(), Box<dyn Error>> {
-    let rows = if app.has_any_query() {
-        let q = build_sorted_filtered_query(
-            vec![
-                (GENRE_COLUMN, &app.genre_query).into(),
-                (TITLE_COLUMN, &app.title_query).into(),
-                (CAST_COLUMN, &app.cast_query).into(),
-                (COUNTRY_COLUMN, &app.country_query).into(),
-                (LANGUAGE_COLUMN, &app.language_query).into(),
-                (PLOT_COLUMN, &app.plot_query).into(),
-            ],
-            &app.item_type,
-        );
-        app.logs.push(Log::Debug(q.to_string()));
-
-        match db.get_no_params_query_result(&q) {
-            Ok(rows) => Ok(rows),
-            Err(err) => {
-                app.logs.push(Log::Error(err.to_string()));
-                db.get_synced_rows_sorted()
-            }
+    let filters = || {
+        app.column_queries()
+            .into_iter()
+            .map(|filter| ColumnFilter::from(filter).fuzzy(app.fuzzy))
+            .collect::<Vec<ColumnFilter>>()
+    };
+    let q = app.range_filters().and_then(|ranges| {
+        if app.suspicious_only {
+            build_suspicious_query(filters(), &ranges, &app.item_type, app.prior_votes)
+        } else if app.has_any_query() {
+            build_sorted_filtered_query(
+                filters(),
+                &ranges,
+                &app.item_type,
+                &app.sort,
+                app.prior_votes,
+            )
+        } else {
+            Ok(build_sorted_query(
+                &app.item_type,
+                &app.sort,
+                app.prior_votes,
+            ))
         }
-    } else {
-        let q = build_sorted_query(&app.item_type);
-        app.logs.push(Log::Debug(q.to_string()));
-
-        match db.get_no_params_query_result(&q) {
-            Ok(rows) => Ok(rows),
-            Err(err) => {
-                app.logs.push(Log::Error(err.to_string()));
-                db.get_synced_rows_sorted()
-            }
+    });
+    let any_terms: Vec<String> = filters()
+        .iter()
+        .flat_map(|filter| filter.any_terms())
+        .collect();
+    // Keep listing the last results while a query is still being typed
+    let q = match q {
+        Ok(q) => q,
+        Err(err) => {
+            app.logs.push(Log::Warn(err.to_string()));
+            app.query_error = Some(err);
+            return Ok(());
+        }
+    };
+    app.query_error = None;
+    app.logs
+        .push(Log::Debug(format!("{} {:?}", q.sql, q.params)));
+
+    let rows = match db.get_query_result(&q) {
+        Ok(rows) => Ok(rows),
+        Err(err) => {
+            app.logs.push(Log::Error(err.to_string()));
+            db.get_synced_rows_sorted()
         }
     }?;
 
+    // Computed once per query instead of on every redraw, as it folds all text columns
+    app.matched_fields = if any_terms.is_empty() {
+        vec![]
+    } else {
+        rows.iter()
+            .map(|row| matched_fields(row, &any_terms))
+            .collect()
+    };
     app.items.unselect();
     app.items.items = rows;
     if !app.items.items.is_empty() {
@@ -81,7 +116,243 @@ fn exec_query(app: &mut App, db: &Db) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-pub fn tui(db: Db) -> Result<(), Box<dyn Error>> {
+/// Re-runs the query and keeps the selected item selected, i.e. after newly rated titles arrived.
+fn refresh_query(app: &mut App, db: &Db) -> Result<(), Box<dyn Error>> {
+    let selected_id = app.selected_item().map(|row| row.id);
+    exec_query(app, db)?;
+    app.data_version = db.data_version()?;
+    app.db_changed = false;
+    if let Some(id) = selected_id {
+        if let Some(idx) = app.items.items.iter().position(|row| row.id == id) {
+            app.items.state.select(Some(idx));
+        }
+    }
+    Ok(())
+}
+
+/// Handles keys while the preset popup is open, which closes once a preset was loaded or saved.
+fn handle_preset_popup_key(app: &mut App, db: &Db, key: KeyEvent) -> Result<(), Box<dyn Error>> {
+    let mut popup = match app.preset_popup.take() {
+        Some(popup) => popup,
+        None => return Ok(()),
+    };
+    let keep_open = match (&mut popup, key.modifiers, key.code) {
+        (_, KeyModifiers::NONE, KeyCode::Esc) => false,
+        (PresetPopup::Load(presets), KeyModifiers::NONE, KeyCode::Down)
+        | (PresetPopup::Load(presets), KeyModifiers::CONTROL, KeyCode::Char('n')) => {
+            presets.next();
+            true
+        }
+        (PresetPopup::Load(presets), KeyModifiers::NONE, KeyCode::Up)
+        | (PresetPopup::Load(presets), KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+            presets.previous();
+            true
+        }
+        (PresetPopup::Load(presets), KeyModifiers::NONE, KeyCode::Enter) => {
+            match presets
+                .state
+                .selected()
+                .and_then(|idx| presets.items.get(idx))
+            {
+                Some(preset) => {
+                    app.logs
+                        .push(Log::Info(format!("loading preset '{}'", preset.name)));
+                    app.apply_preset(preset);
+                    exec_query(app, db)?;
+                    false
+                }
+                None => true,
+            }
+        }
+        (PresetPopup::Load(presets), KeyModifiers::NONE, KeyCode::Delete) => {
+            if let Some(idx) = presets.state.selected() {
+                if idx < presets.items.len() {
+                    let preset = presets.items.remove(idx);
+                    db.delete_preset(&preset.name)?;
+                    presets.unselect();
+                    if !presets.items.is_empty() {
+                        presets.state.select(Some(idx.min(presets.items.len() - 1)));
+                    }
+                }
+            }
+            true
+        }
+        (PresetPopup::Save(name), KeyModifiers::NONE, KeyCode::Enter) => {
+            let name = name.trim();
+            if name.is_empty() {
+                true
+            } else {
+                app.logs
+                    .push(Log::Info(format!("saving preset '{}'", name)));
+                db.save_preset(&app.preset(name))?;
+                false
+            }
+        }
+        (PresetPopup::Save(name), KeyModifiers::NONE, KeyCode::Backspace) => {
+            name.pop();
+            true
+        }
+        (PresetPopup::Save(name), KeyModifiers::NONE, KeyCode::Char(c))
+        | (PresetPopup::Save(name), KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            name.push(c);
+            true
+        }
+        _ => true,
+    };
+    if keep_open {
+        app.preset_popup = Some(popup);
+    }
+    Ok(())
+}
+
+fn start_background_sync(app: &mut App, background_sync: &BackgroundSync, opts: SyncOptions) {
+    if app.sync_events.is_some() {
+        app.logs
+            .push(Log::Warn("sync is running already".to_string()));
+        return;
+    }
+    if background_sync.api_keys.is_empty() {
+        let mut sync_progress = SyncProgress::new("Cannot sync, the OMDB_KEY is missing");
+        sync_progress.finished = true;
+        app.sync_progress = Some(sync_progress);
+        return;
+    }
+
+    let api_keys = background_sync.api_keys.clone();
+    // The TUI opened the database already, creating it on first launch, so the sync's
+    // connection mustn't fill it with the Netflix titles again
+    let db_info = DatabaseInfo {
+        db_exists: true,
+        folder_exists: true,
+        ..background_sync.db_info.clone()
+    };
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        // The sync writes through its own connection while the TUI keeps reading through its one
+        let result = SyncLock::acquire(&db_info.db_path).and_then(|_lock| {
+            let db = Db::new(&db_info)?;
+            sync_ratings_with_events(&db, &api_keys, opts, |event| {
+                tx.send(event).ok();
+            })
+        });
+        let event = match result {
+            Ok(report) => SyncEvent::Finished(report.outcome),
+            Err(err) => SyncEvent::Failed(err.to_string()),
+        };
+        tx.send(event).ok();
+    });
+
+    app.sync_events = Some(rx);
+    app.sync_progress = Some(SyncProgress::new("Starting sync"));
+}
+
+/// Notices when another process wrote to the database so the user can reload the results.
+/// Our own background sync is not reported as it refreshes the results once it finishes.
+fn check_db_changes(app: &mut App, db: &Db) -> Result<(), Box<dyn Error>> {
+    let data_version = db.data_version()?;
+    if data_version != app.data_version {
+        app.data_version = data_version;
+        if app.sync_events.is_none() {
+            app.db_changed = true;
+        }
+    }
+    Ok(())
+}
+
+/// Applies the events the background sync sent since the last call and re-runs the query
+/// if titles got rated, deleted or touched so that the list shows them as they are now.
+fn process_sync_events(app: &mut App, db: &Db) -> Result<(), Box<dyn Error>> {
+    let events: Vec<SyncEvent> = match &app.sync_events {
+        Some(sync_events) => sync_events.try_iter().collect(),
+        None => return Ok(()),
+    };
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let mut rows_changed = false;
+    let sync_progress = app
+        .sync_progress
+        .get_or_insert_with(|| SyncProgress::new(""));
+    for event in events {
+        match event {
+            SyncEvent::Started {
+                candidates,
+                refresh,
+                total,
+                ..
+            } => {
+                sync_progress.total = total;
+                let kind = if refresh { "selected" } else { "unsynced" };
+                sync_progress.status = format!("Syncing {} of {} {}", total, candidates, kind);
+            }
+            SyncEvent::NoRequestsLeft { resets_in } => {
+                sync_progress.status =
+                    format!("No requests left, resets in {}", format_duration(resets_in));
+            }
+            SyncEvent::Pending { .. } => sync_progress.done += 1,
+            SyncEvent::Synced { typ, row, error } => {
+                sync_progress.done += 1;
+                match typ {
+                    SyncImdbResultType::Success => {
+                        sync_progress.synced += 1;
+                        rows_changed = true;
+                    }
+                    // Deleted if they never synced, otherwise no longer stale
+                    SyncImdbResultType::NotFound | SyncImdbResultType::MissingImdbData => {
+                        rows_changed = true;
+                    }
+                    _ => {}
+                }
+                sync_progress.status = format!(
+                    "{}/{} synced, last '{}'",
+                    sync_progress.synced, sync_progress.total, row.title
+                );
+                if let Some(error) = error {
+                    app.logs.push(Log::Error(error));
+                }
+            }
+            SyncEvent::KeyExhausted { key, keys } => {
+                sync_progress.status = format!("Key {} of {} used up, switching keys", key, keys);
+            }
+            SyncEvent::RateLimitExceeded { resets_in } => {
+                sync_progress.status = format!(
+                    "Rate limit exceeded, resets in {}",
+                    format_duration(resets_in)
+                );
+            }
+            SyncEvent::Finished(outcome) => {
+                sync_progress.finished = true;
+                let done = match outcome {
+                    SyncOutcome::Done => "Sync done",
+                    SyncOutcome::RateLimited => "Sync stopped at rate limit",
+                    SyncOutcome::Errors => "Sync done with errors",
+                };
+                sync_progress.status = format!("{}, {} synced", done, sync_progress.synced);
+            }
+            SyncEvent::Failed(err) => {
+                sync_progress.finished = true;
+                sync_progress.status = format!("Sync failed: {}", err);
+            }
+        }
+    }
+    if sync_progress.finished {
+        app.sync_events = None;
+    }
+
+    if rows_changed {
+        refresh_query(app, db)?;
+    }
+    Ok(())
+}
+
+pub fn tui(
+    db: Db,
+    item_type: ItemType,
+    sort: Sort,
+    prior_votes: u32,
+    background_sync: BackgroundSync,
+) -> Result<(), Box<dyn Error>> {
     let _show_log: bool = false;
     #[cfg(feature = "log")]
     let _show_log: bool = true;
@@ -102,7 +373,11 @@ Make sure sync them by running 'nf-rated sync' first!"
     let mut terminal = Terminal::new(backend)?;
 
     let mut app = App::new(all_rows);
-    app.items.state.select(Some(0));
+    app.item_type = item_type;
+    app.sort = sort;
+    app.prior_votes = prior_votes;
+    exec_query(&mut app, &db)?;
+    app.data_version = db.data_version()?;
 
     let mut current_summary_size: Rect = Default::default();
     let constraints = if _show_log {
@@ -117,6 +392,8 @@ Make sure sync them by running 'nf-rated sync' first!"
 
     terminal.clear()?;
     loop {
+        check_db_changes(&mut app, &db)?;
+        process_sync_events(&mut app, &db)?;
         terminal.draw(|mut f| {
             let main_container = Layout::default()
                 .direction(Direction::Vertical)
@@ -145,10 +422,20 @@ Make sure sync them by running 'nf-rated sync' first!"
             if _show_log {
                 f.render_widget(render_log(&app.logs), log_container)
             };
+
+            if let Some(preset_popup) = &mut app.preset_popup {
+                render_preset_popup(f, preset_popup, summary_and_config_container);
+            }
         })?;
 
         if poll(Duration::from_millis(200))? {
             let event = read()?;
+            if app.preset_popup.is_some() {
+                if let Event::Key(key) = event {
+                    handle_preset_popup_key(&mut app, &db, key)?;
+                }
+                continue;
+            }
             match event {
                 //
                 // Quit
@@ -219,6 +506,103 @@ Make sure sync them by running 'nf-rated sync' first!"
                     exec_query(&mut app, &db)?;
                 }
 
+                //
+                // Sort
+                //
+                Event::Key(KeyEvent {
+                    modifiers: KeyModifiers::CONTROL,
+                    code: KeyCode::Char('s'),
+                }) => {
+                    app.next_sort();
+                    exec_query(&mut app, &db)?;
+                }
+                Event::Key(KeyEvent {
+                    modifiers: KeyModifiers::CONTROL,
+                    code: KeyCode::Char('t'),
+                }) => {
+                    app.toggle_sort_direction();
+                    exec_query(&mut app, &db)?;
+                }
+
+                //
+                // Fuzzy search
+                //
+                Event::Key(KeyEvent {
+                    modifiers: KeyModifiers::CONTROL,
+                    code: KeyCode::Char('f'),
+                }) => {
+                    app.fuzzy = !app.fuzzy;
+                    exec_query(&mut app, &db)?;
+                }
+
+                //
+                // Presets
+                //
+                Event::Key(KeyEvent {
+                    modifiers: KeyModifiers::CONTROL,
+                    code: KeyCode::Char('g'),
+                }) => {
+                    let mut presets = StatefulList::with_items(db.get_presets()?);
+                    if !presets.items.is_empty() {
+                        presets.next();
+                    }
+                    app.preset_popup = Some(PresetPopup::Load(presets));
+                }
+                Event::Key(KeyEvent {
+                    modifiers: KeyModifiers::CONTROL,
+                    code: KeyCode::Char('w'),
+                }) => {
+                    app.preset_popup = Some(PresetPopup::Save("".to_string()));
+                }
+
+                //
+                // Audit matches
+                //
+                Event::Key(KeyEvent {
+                    modifiers: KeyModifiers::CONTROL,
+                    code: KeyCode::Char('a'),
+                }) => {
+                    app.suspicious_only = !app.suspicious_only;
+                    exec_query(&mut app, &db)?;
+                }
+
+                //
+                // Sync ratings
+                //
+                Event::Key(KeyEvent {
+                    modifiers: KeyModifiers::CONTROL,
+                    code: KeyCode::Char('y'),
+                }) => {
+                    start_background_sync(&mut app, &background_sync, background_sync.opts.clone());
+                }
+                Event::Key(KeyEvent {
+                    modifiers: KeyModifiers::CONTROL,
+                    code: KeyCode::Char('r'),
+                }) => {
+                    if let Some(id) = app.selected_item().map(|row| row.id) {
+                        // Re-syncing is about getting fresh data, so skip cached responses
+                        let opts = SyncOptions {
+                            cache: None,
+                            selection: SyncSelection {
+                                ids: vec![id],
+                                ..Default::default()
+                            },
+                            ..background_sync.opts.clone()
+                        };
+                        start_background_sync(&mut app, &background_sync, opts);
+                    }
+                }
+
+                //
+                // Reload after another process changed ratings
+                //
+                Event::Key(KeyEvent {
+                    modifiers: KeyModifiers::CONTROL,
+                    code: KeyCode::Char('l'),
+                }) => {
+                    refresh_query(&mut app, &db)?;
+                }
+
                 //
                 // Navigate filter inputs
                 //